@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+/// What an `AudioBuffer` does when the producer and the consumer run at different rates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// A full buffer discards its oldest sample to make room, so latency never grows. An empty
+    /// one yields nothing.
+    #[default]
+    DropOldest,
+    /// An empty buffer keeps repeating the last sample it yielded, avoiding clicks on underruns.
+    /// A full one discards new samples.
+    DuplicateLast,
+    /// A full buffer refuses new samples, so the producer has to wait for the consumer. An empty
+    /// one yields nothing.
+    Block,
+}
+
+/// A bounded queue of audio samples between an emulator and the host's audio output.
+#[derive(Debug, Clone)]
+pub struct AudioBuffer<T> {
+    samples: VecDeque<T>,
+    capacity: usize,
+    overflow: Overflow,
+    last: Option<T>,
+}
+
+impl<T: Copy> AudioBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        AudioBuffer::with_overflow(capacity, Overflow::default())
+    }
+
+    pub fn with_overflow(capacity: usize, overflow: Overflow) -> Self {
+        AudioBuffer {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            overflow,
+            last: None,
+        }
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of samples waiting to be consumed, for front-ends adapting their pacing to it.
+    pub fn fill_level(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Queues `sample`, returning whether it was stored.
+    pub fn push(&mut self, sample: T) -> bool {
+        if self.samples.len() >= self.capacity {
+            match self.overflow {
+                Overflow::DropOldest if self.capacity > 0 => {
+                    self.samples.pop_front();
+                }
+                _ => return false,
+            }
+        }
+
+        self.samples.push_back(sample);
+
+        true
+    }
+
+    /// Takes the oldest queued sample.
+    pub fn pop(&mut self) -> Option<T> {
+        match self.samples.pop_front() {
+            Some(sample) => {
+                self.last = Some(sample);
+
+                Some(sample)
+            }
+            None if self.overflow == Overflow::DuplicateLast => self.last,
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_oldest_keeps_most_recent_samples() {
+        let mut buffer = AudioBuffer::new(4);
+
+        for sample in 0..6i16 {
+            assert!(buffer.push(sample));
+        }
+
+        assert_eq!(buffer.fill_level(), 4);
+        assert_eq!(
+            std::iter::from_fn(|| buffer.pop()).collect::<Vec<_>>(),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(buffer.fill_level(), 0);
+    }
+
+    #[test]
+    fn test_duplicate_last() {
+        let mut buffer = AudioBuffer::with_overflow(2, Overflow::DuplicateLast);
+
+        assert_eq!(buffer.pop(), None);
+        assert!(buffer.push(1i16));
+        assert!(buffer.push(2));
+        assert!(!buffer.push(3));
+        assert_eq!(buffer.pop(), Some(1));
+        assert_eq!(buffer.pop(), Some(2));
+        assert_eq!(buffer.pop(), Some(2));
+        assert_eq!(buffer.fill_level(), 0);
+    }
+
+    #[test]
+    fn test_block() {
+        let mut buffer = AudioBuffer::with_overflow(1, Overflow::Block);
+
+        assert!(buffer.push(1i16));
+        assert!(!buffer.push(2));
+        assert_eq!(buffer.fill_level(), 1);
+        assert_eq!(buffer.pop(), Some(1));
+        assert_eq!(buffer.pop(), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{OniError, Result};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// Reads a ROM out of a zip or gzip archive at `path`.
+pub fn load_rom_from_archive<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    read_rom_from_archive(File::open(path)?)
+}
+
+/// Reads a ROM out of a zip or gzip archive, telling the two apart by their magic bytes.
+///
+/// A zip archive has to contain exactly one `.gb` or `.gbc` entry.
+pub fn read_rom_from_archive<R: Read + Seek>(mut reader: R) -> Result<Vec<u8>> {
+    let mut magic = [0; 4];
+
+    reader.read_exact(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut rom = Vec::new();
+
+    if magic == ZIP_MAGIC {
+        let mut archive = ZipArchive::new(reader)
+            .map_err(|error| OniError::InvalidRomArchive(error.to_string()))?;
+        let roms = archive
+            .file_names()
+            .filter(|name| {
+                let name = name.to_ascii_lowercase();
+
+                name.ends_with(".gb") || name.ends_with(".gbc")
+            })
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let name = match roms.as_slice() {
+            [name] => name,
+            [] => {
+                return Err(OniError::InvalidRomArchive(
+                    "archive doesn't contain a ROM".to_string(),
+                ))
+            }
+            _ => {
+                return Err(OniError::InvalidRomArchive(
+                    "archive contains more than one ROM".to_string(),
+                ))
+            }
+        };
+
+        archive
+            .by_name(name)
+            .map_err(|error| OniError::InvalidRomArchive(error.to_string()))?
+            .read_to_end(&mut rom)?;
+    } else if magic[..2] == GZIP_MAGIC {
+        GzDecoder::new(reader).read_to_end(&mut rom)?;
+    } else {
+        return Err(OniError::InvalidRomArchive(
+            "unrecognized archive format".to_string(),
+        ));
+    }
+
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn rom() -> Vec<u8> {
+        (0..0x8000).map(|index| (index % 251) as u8).collect()
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, content) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_zip() {
+        let rom = rom();
+        let archive = zip(&[("readme.txt", b"hello"), ("game.GB", &rom)]);
+
+        assert_eq!(read_rom_from_archive(Cursor::new(archive)).unwrap(), rom);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let rom = rom();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+        encoder.write_all(&rom).unwrap();
+
+        let archive = encoder.finish().unwrap();
+
+        assert_eq!(read_rom_from_archive(Cursor::new(archive)).unwrap(), rom);
+    }
+
+    #[test]
+    fn test_ambiguous_zip() {
+        let archive = zip(&[("a.gb", &[0x00]), ("b.gbc", &[0x00])]);
+
+        assert!(matches!(
+            read_rom_from_archive(Cursor::new(archive)),
+            Err(OniError::InvalidRomArchive(_))
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::error::{OniError, Result};
+
+const HEADER_END: usize = 0x0150;
+
+/// Logo the boot ROM compares against 0x0104-0x0133 before handing control to the cartridge.
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MbcType {
+    None,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseeCode {
+    Old(u8),
+    New([u8; 2]),
+}
+
+impl LicenseeCode {
+    /// Name of the publisher, if the code is a well-known one.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            LicenseeCode::Old(code) => match code {
+                0x01 | 0x31 => Some("Nintendo"),
+                0x08 | 0x38 => Some("Capcom"),
+                0x13 | 0x69 => Some("Electronic Arts"),
+                0x18 => Some("Hudson Soft"),
+                0x28 | 0x7F | 0xC2 => Some("Kemco"),
+                0x34 | 0xA4 => Some("Konami"),
+                0x41 => Some("Ubi Soft"),
+                0x51 | 0xB0 => Some("Acclaim"),
+                0x52 => Some("Activision"),
+                0x56 | 0xDB | 0xFF => Some("LJN"),
+                0x67 => Some("Ocean"),
+                0x70 => Some("Infogrames"),
+                0x78 => Some("THQ"),
+                0x79 => Some("Accolade"),
+                0x8B => Some("Bullet-Proof Software"),
+                0x9B => Some("Tecmo"),
+                0xAF => Some("Namco"),
+                0xB6 => Some("HAL Laboratory"),
+                0xB7 => Some("SNK"),
+                0xBB => Some("Sunsoft"),
+                0xC0 | 0xD0 => Some("Taito"),
+                0xC3 => Some("Squaresoft"),
+                0xC5 => Some("Data East"),
+                0xE9 => Some("Natsume"),
+                0xEB => Some("Atlus"),
+                _ => None,
+            },
+            LicenseeCode::New(code) => match code {
+                b"01" | b"31" => Some("Nintendo"),
+                b"08" => Some("Capcom"),
+                b"13" | b"69" => Some("Electronic Arts"),
+                b"18" | b"38" => Some("Hudson Soft"),
+                b"28" => Some("Kemco"),
+                b"32" => Some("Bandai"),
+                b"34" | b"54" | b"A4" => Some("Konami"),
+                b"41" => Some("Ubi Soft"),
+                b"42" => Some("Atlus"),
+                b"51" => Some("Acclaim"),
+                b"52" => Some("Activision"),
+                b"56" => Some("LJN"),
+                b"67" => Some("Ocean"),
+                b"70" => Some("Infogrames"),
+                b"78" => Some("THQ"),
+                b"79" => Some("Accolade"),
+                b"91" => Some("Chunsoft"),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    logo: [u8; 48],
+    title: String,
+    licensee_code: LicenseeCode,
+    mbc_type: MbcType,
+    destination: Destination,
+    mask_rom_version: u8,
+    global_checksum: u16,
+    computed_global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader> {
+        if rom.len() < HEADER_END {
+            return Err(OniError::InvalidCartridgeHeader(
+                "ROM is too small to contain a header",
+            ));
+        }
+
+        let mut logo = [0; 48];
+
+        logo.copy_from_slice(&rom[0x0104..0x0134]);
+
+        // On CGB cartridges the last byte of the title area is the CGB flag.
+        let title_end = if rom[0x0143] & 0x80 != 0 {
+            0x0143
+        } else {
+            0x0144
+        };
+        let title = rom[0x0134..title_end]
+            .iter()
+            .take_while(|&&byte| byte != 0x00)
+            .map(|&byte| byte as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        let licensee_code = match rom[0x014B] {
+            0x33 => LicenseeCode::New([rom[0x0144], rom[0x0145]]),
+            code => LicenseeCode::Old(code),
+        };
+
+        let mbc_type = match rom[0x0147] {
+            0x00 | 0x08 | 0x09 => MbcType::None,
+            0x01..=0x03 => MbcType::Mbc1,
+            0x05 | 0x06 => MbcType::Mbc2,
+            0x0B..=0x0D => MbcType::Mmm01,
+            0x0F..=0x13 => MbcType::Mbc3,
+            0x19..=0x1E => MbcType::Mbc5,
+            0x20 => MbcType::Mbc6,
+            0x22 => MbcType::Mbc7,
+            0xFC => MbcType::PocketCamera,
+            0xFD => MbcType::Tama5,
+            0xFE => MbcType::HuC3,
+            0xFF => MbcType::HuC1,
+            cartridge_type => return Err(OniError::UnsupportedMbc(cartridge_type)),
+        };
+
+        let destination = match rom[0x014A] {
+            0x00 => Destination::Japan,
+            _ => Destination::Overseas,
+        };
+
+        let global_checksum = u16::from_be_bytes([rom[0x014E], rom[0x014F]]);
+        let computed_global_checksum = rom
+            .iter()
+            .enumerate()
+            .filter(|(address, _)| *address != 0x014E && *address != 0x014F)
+            .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16));
+
+        Ok(CartridgeHeader {
+            logo,
+            title,
+            licensee_code,
+            mbc_type,
+            destination,
+            mask_rom_version: rom[0x014C],
+            global_checksum,
+            computed_global_checksum,
+        })
+    }
+
+    /// Whether the logo at 0x0104-0x0133 is the one the boot ROM expects.
+    pub fn logo_matches(&self) -> bool {
+        self.logo == NINTENDO_LOGO
+    }
+
+    /// Title with the padding stripped.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn licensee_code(&self) -> LicenseeCode {
+        self.licensee_code
+    }
+
+    pub fn licensee_name(&self) -> Option<&'static str> {
+        self.licensee_code.name()
+    }
+
+    pub fn mbc_type(&self) -> MbcType {
+        self.mbc_type
+    }
+
+    pub fn destination(&self) -> Destination {
+        self.destination
+    }
+
+    pub fn mask_rom_version(&self) -> u8 {
+        self.mask_rom_version
+    }
+
+    pub fn global_checksum(&self) -> u16 {
+        self.global_checksum
+    }
+
+    /// Whether the checksum stored at 0x014E-0x014F matches the sum of every other ROM byte.
+    ///
+    /// The boot ROM doesn't verify it, so a mismatch doesn't prevent a game from running.
+    pub fn global_checksum_matches(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(title: &[u8], cartridge_type: u8, old_licensee_code: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title);
+        rom[0x0147] = cartridge_type;
+        rom[0x014A] = 0x01;
+        rom[0x014B] = old_licensee_code;
+        rom[0x014C] = 0x01;
+        rom[0x7FFF] = 0xAB;
+
+        let checksum = rom
+            .iter()
+            .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16));
+
+        rom[0x014E..0x0150].copy_from_slice(&checksum.to_be_bytes());
+
+        rom
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = CartridgeHeader::parse(&rom(b"TETRIS", 0x00, 0x01)).unwrap();
+
+        assert_eq!(header.title(), "TETRIS");
+        assert_eq!(header.licensee_code(), LicenseeCode::Old(0x01));
+        assert_eq!(header.licensee_name(), Some("Nintendo"));
+        assert_eq!(header.mbc_type(), MbcType::None);
+        assert_eq!(header.destination(), Destination::Overseas);
+        assert_eq!(header.mask_rom_version(), 0x01);
+        assert!(header.global_checksum_matches());
+        assert!(header.logo_matches());
+    }
+
+    #[test]
+    fn test_corrupted_logo() {
+        let mut rom = rom(b"TETRIS", 0x00, 0x01);
+
+        rom[0x0110] ^= 0xFF;
+
+        assert!(!CartridgeHeader::parse(&rom).unwrap().logo_matches());
+    }
+
+    #[test]
+    fn test_new_licensee_code() {
+        let mut rom = rom(b"POKEMON RED", 0x13, 0x33);
+
+        rom[0x0144..0x0146].copy_from_slice(b"01");
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.licensee_code(), LicenseeCode::New(*b"01"));
+        assert_eq!(header.licensee_name(), Some("Nintendo"));
+        assert_eq!(header.mbc_type(), MbcType::Mbc3);
+        assert!(!header.global_checksum_matches());
+    }
+
+    #[test]
+    fn test_cgb_flag_is_not_part_of_title() {
+        let mut rom = rom(b"ABCDEFGHIJKLMNO", 0x1B, 0x00);
+
+        rom[0x0143] = 0x80;
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.title(), "ABCDEFGHIJKLMNO");
+        assert_eq!(header.mbc_type(), MbcType::Mbc5);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            CartridgeHeader::parse(&[0; 0x100]),
+            Err(OniError::InvalidCartridgeHeader(_))
+        ));
+        assert!(matches!(
+            CartridgeHeader::parse(&rom(b"", 0x04, 0x00)),
+            Err(OniError::UnsupportedMbc(0x04))
+        ));
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(feature = "zip")]
+mod archive;
+mod header;
+
+#[cfg(feature = "zip")]
+pub use archive::*;
+pub use header::*;
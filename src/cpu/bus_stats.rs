@@ -0,0 +1,54 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    Rom,
+    Vram,
+    ExternalRam,
+    Wram,
+    Oam,
+    Unusable,
+    Io,
+    Hram,
+}
+
+impl MemoryRegion {
+    const COUNT: usize = 8;
+
+    /// Region `address` belongs to, counting echo RAM as WRAM and IE as I/O.
+    pub fn from_address(address: u16) -> MemoryRegion {
+        match address {
+            0x0000..=0x7FFF => MemoryRegion::Rom,
+            0x8000..=0x9FFF => MemoryRegion::Vram,
+            0xA000..=0xBFFF => MemoryRegion::ExternalRam,
+            0xC000..=0xFDFF => MemoryRegion::Wram,
+            0xFE00..=0xFE9F => MemoryRegion::Oam,
+            0xFEA0..=0xFEFF => MemoryRegion::Unusable,
+            0xFF00..=0xFF7F | 0xFFFF => MemoryRegion::Io,
+            0xFF80..=0xFFFE => MemoryRegion::Hram,
+        }
+    }
+}
+
+/// Number of reads and writes that went to each memory region, instruction fetches included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BusStats {
+    reads: [u64; MemoryRegion::COUNT],
+    writes: [u64; MemoryRegion::COUNT],
+}
+
+impl BusStats {
+    pub fn reads(&self, region: MemoryRegion) -> u64 {
+        self.reads[region as usize]
+    }
+
+    pub fn writes(&self, region: MemoryRegion) -> u64 {
+        self.writes[region as usize]
+    }
+
+    pub(super) fn record_read(&mut self, address: u16) {
+        self.reads[MemoryRegion::from_address(address) as usize] += 1;
+    }
+
+    pub(super) fn record_write(&mut self, address: u16) {
+        self.writes[MemoryRegion::from_address(address) as usize] += 1;
+    }
+}
@@ -0,0 +1,96 @@
+use crate::error::{OniError, Result};
+
+const ROM_END: u16 = 0x7FFF;
+
+/// A Game Genie code, replacing the byte read from a ROM address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameGenieCode {
+    pub address: u16,
+    pub value: u8,
+    /// The byte the ROM has to hold for the patch to apply. Codes without one always apply.
+    pub compare: Option<u8>,
+}
+
+impl GameGenieCode {
+    /// Parses a code in the `ABC-DEF` or `ABC-DEF-GHI` format.
+    ///
+    /// `AB` is the new value, `FCDE` the address with `F` inverted and `GI` the compare value,
+    /// rotated left by two and XORed with 0xBA. `H` is ignored.
+    pub fn parse(code: &str) -> Result<GameGenieCode> {
+        let invalid = || OniError::InvalidCheatCode(code.to_string());
+        let digits = code
+            .split('-')
+            .map(|group| (group.len() == 3).then_some(group).ok_or_else(invalid))
+            .collect::<Result<String>>()?
+            .chars()
+            .map(|digit| digit.to_digit(16).map(|digit| digit as u16))
+            .collect::<Option<Vec<u16>>>()
+            .ok_or_else(invalid)?;
+
+        if digits.len() != 6 && digits.len() != 9 {
+            return Err(invalid());
+        }
+
+        let address = ((digits[5] ^ 0xF) << 12) | (digits[2] << 8) | (digits[3] << 4) | digits[4];
+
+        if address > ROM_END {
+            return Err(invalid());
+        }
+
+        let compare = (digits.len() == 9)
+            .then(|| ((digits[6] << 4 | digits[8]) as u8).rotate_right(2) ^ 0xBA);
+
+        Ok(GameGenieCode {
+            address,
+            value: (digits[0] << 4 | digits[1]) as u8,
+            compare,
+        })
+    }
+
+    /// The byte read from `address` when the ROM holds `value` there.
+    pub fn patch(&self, address: u16, value: u8) -> u8 {
+        if address == self.address && self.compare.is_none_or(|compare| compare == value) {
+            self.value
+        } else {
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            GameGenieCode::parse("3E1-50B-8EA").unwrap(),
+            GameGenieCode {
+                address: 0x4150,
+                value: 0x3E,
+                compare: Some(0x18),
+            }
+        );
+        assert_eq!(
+            GameGenieCode::parse("00a-17f").unwrap(),
+            GameGenieCode {
+                address: 0x0A17,
+                value: 0x00,
+                compare: None,
+            }
+        );
+        assert!(GameGenieCode::parse("3E1-50B-8E").is_err());
+        assert!(GameGenieCode::parse("3E150B8EA").is_err());
+        assert!(GameGenieCode::parse("3G1-50B").is_err());
+        assert!(GameGenieCode::parse("3E1-507").is_err());
+    }
+
+    #[test]
+    fn test_patch() {
+        let code = GameGenieCode::parse("3E1-50B-8EA").unwrap();
+
+        assert_eq!(code.patch(0x4150, 0x18), 0x3E);
+        assert_eq!(code.patch(0x4150, 0x19), 0x19);
+        assert_eq!(code.patch(0x4151, 0x18), 0x18);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::cpu::Flags;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    NZ,
+    Z,
+    NC,
+    C,
+}
+
+impl Condition {
+    pub fn is_met(&self, flags: &Flags) -> bool {
+        match self {
+            Condition::NZ => !flags.z,
+            Condition::Z => flags.z,
+            Condition::NC => !flags.cy,
+            Condition::C => flags.cy,
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_met() {
+        let flags = Flags {
+            z: true,
+            n: false,
+            h: false,
+            cy: false,
+        };
+
+        assert!(!Condition::NZ.is_met(&flags));
+        assert!(Condition::Z.is_met(&flags));
+        assert!(Condition::NC.is_met(&flags));
+        assert!(!Condition::C.is_met(&flags));
+    }
+}
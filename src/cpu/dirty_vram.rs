@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+
+const TILE_DATA_START: u16 = 0x8000;
+const TILE_MAP_START: u16 = 0x9800;
+const VRAM_END: u16 = 0x9FFF;
+const TILE_SIZE: u16 = 16;
+
+/// Tiles and tile map entries written to since the last `Cpu::take_dirty_vram`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirtyVram {
+    tiles: BTreeSet<u16>,
+    tile_map_entries: BTreeSet<u16>,
+}
+
+impl DirtyVram {
+    /// Indices of the dirty 16-byte tile slots, counted from 0x8000.
+    pub fn tiles(&self) -> &BTreeSet<u16> {
+        &self.tiles
+    }
+
+    /// Indices of the dirty tile map entries, counted from 0x9800 and spanning both maps.
+    pub fn tile_map_entries(&self) -> &BTreeSet<u16> {
+        &self.tile_map_entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty() && self.tile_map_entries.is_empty()
+    }
+
+    pub(super) fn record_write(&mut self, address: u16) {
+        match address {
+            TILE_DATA_START..=0x97FF => {
+                self.tiles.insert((address - TILE_DATA_START) / TILE_SIZE);
+            }
+            TILE_MAP_START..=VRAM_END => {
+                self.tile_map_entries.insert(address - TILE_MAP_START);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_write() {
+        let mut dirty_vram = DirtyVram::default();
+
+        dirty_vram.record_write(0x8000);
+        dirty_vram.record_write(0x800F);
+        dirty_vram.record_write(0x97FF);
+        dirty_vram.record_write(0x9800);
+        dirty_vram.record_write(0x9C01);
+        dirty_vram.record_write(0xA000);
+
+        assert_eq!(
+            dirty_vram.tiles().iter().copied().collect::<Vec<_>>(),
+            vec![0, 383]
+        );
+        assert_eq!(
+            dirty_vram
+                .tile_map_entries()
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![0x000, 0x401]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::cpu::Instruction;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::Cursor;
+
+const BANK_SIZE: usize = 0x4000;
+const SWITCHABLE_BANK_START: u16 = 0x4000;
+const SWITCHABLE_BANK_END: u16 = 0x7FFF;
+
+/// An address qualified with the ROM bank it refers to, if it lies in ROM and the bank is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankedAddress {
+    pub bank: Option<u16>,
+    pub address: u16,
+}
+
+impl BankedAddress {
+    /// Qualifies `address` as seen from code in `bank`.
+    ///
+    /// Code in bank 0 can't tell which bank is mapped into the switchable region, so switchable
+    /// addresses seen from it are left without a bank.
+    pub fn new(bank: u16, address: u16) -> BankedAddress {
+        let bank = match address {
+            0x0000..=0x3FFF => Some(0),
+            SWITCHABLE_BANK_START..=SWITCHABLE_BANK_END if bank != 0 => Some(bank),
+            _ => None,
+        };
+
+        BankedAddress { bank, address }
+    }
+}
+
+impl Display for BankedAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{:02X}:{:04X}", bank, self.address),
+            None if (SWITCHABLE_BANK_START..=SWITCHABLE_BANK_END).contains(&self.address) => {
+                write!(f, "??:{:04X}", self.address)
+            }
+            None => write!(f, "{:04X}", self.address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledLine {
+    pub address: BankedAddress,
+    pub bytes: Vec<u8>,
+    /// `None` if the bank ends in the middle of an instruction.
+    pub instruction: Option<Instruction>,
+    /// Where the instruction jumps, calls or restarts to, resolved against the same bank.
+    pub target: Option<BankedAddress>,
+}
+
+impl Display for DisassembledLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.instruction {
+            Some(instruction) => write!(f, "{} {}", self.address, instruction),
+            None => write!(f, "{} DB ${:02X}", self.address, self.bytes[0]),
+        }
+    }
+}
+
+/// Linearly disassembles ROM bank `bank` of `rom`.
+///
+/// Bank 0 is disassembled at 0x0000-0x3FFF, every other bank at 0x4000-0x7FFF, where it would be
+/// mapped while switched in.
+pub fn disassemble(rom: &[u8], bank: u16) -> Vec<DisassembledLine> {
+    let offset = bank as usize * BANK_SIZE;
+    let bytes = rom
+        .get(offset..(offset + BANK_SIZE).min(rom.len()))
+        .unwrap_or(&[]);
+    let base = if bank == 0 { 0 } else { SWITCHABLE_BANK_START };
+    let mut memory = Cursor::new(bytes);
+    let mut lines = Vec::new();
+
+    while (memory.position() as usize) < bytes.len() {
+        let position = memory.position() as usize;
+        let address = base + position as u16;
+
+        let line = match Instruction::decode_with_bytes(&mut memory) {
+            Ok((instruction, instruction_bytes)) => {
+                let target = instruction
+                    .branch_target(address)
+                    .map(|target| BankedAddress::new(bank, target));
+
+                DisassembledLine {
+                    address: BankedAddress::new(bank, address),
+                    bytes: instruction_bytes,
+                    instruction: Some(instruction),
+                    target,
+                }
+            }
+            // An instruction cut off by the end of the bank is emitted one byte at a time as data.
+            Err(_) => {
+                memory.set_position(position as u64 + 1);
+
+                DisassembledLine {
+                    address: BankedAddress::new(bank, address),
+                    bytes: vec![bytes[position]],
+                    instruction: None,
+                    target: None,
+                }
+            }
+        };
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// RST targets, interrupt vectors and the cartridge entry point, which are reachable without any
+/// jump leading to them.
+const DEFAULT_ENTRY_POINTS: [u16; 14] = [
+    0x0000, 0x0008, 0x0010, 0x0018, 0x0020, 0x0028, 0x0030, 0x0038, 0x0040, 0x0048, 0x0050, 0x0058,
+    0x0060, 0x0100,
+];
+
+/// Recursive-traversal disassembler over a flat address space.
+///
+/// Only code reachable from the entry points by following branches and fallthroughs is decoded,
+/// so data embedded between routines isn't mistaken for instructions. Code only reached through
+/// `JP HL` or a pushed return address has to be seeded with `add_entry_point`.
+pub struct Disassembler<'a> {
+    memory: &'a [u8],
+    instructions: BTreeMap<u16, Instruction>,
+    code: Vec<bool>,
+    pending: Vec<u16>,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(memory: &'a [u8]) -> Disassembler<'a> {
+        Disassembler {
+            memory,
+            instructions: BTreeMap::new(),
+            code: vec![false; memory.len()],
+            pending: DEFAULT_ENTRY_POINTS.to_vec(),
+        }
+    }
+
+    pub fn add_entry_point(&mut self, address: u16) {
+        self.pending.push(address);
+    }
+
+    /// Follows every pending entry point until no new code is found.
+    pub fn run(&mut self) {
+        while let Some(address) = self.pending.pop() {
+            if self.instructions.contains_key(&address) {
+                continue;
+            }
+
+            let bytes = match self.memory.get(address as usize..) {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+            let (instruction, length) = match Instruction::decode_slice(bytes) {
+                Ok(decoded) => decoded,
+                Err(_) => continue,
+            };
+
+            for flag in &mut self.code[address as usize..address as usize + length] {
+                *flag = true;
+            }
+
+            if let Some(target) = instruction.branch_target(address) {
+                self.pending.push(target);
+            }
+
+            if !instruction.is_terminator() {
+                self.pending.push(address.wrapping_add(length as u16));
+            }
+
+            self.instructions.insert(address, instruction);
+        }
+    }
+
+    /// Reachable instructions found so far, keyed by their address.
+    pub fn instructions(&self) -> &BTreeMap<u16, Instruction> {
+        &self.instructions
+    }
+
+    /// Address of the instruction following the one decoded at `address`.
+    fn next_instruction_address(&self, address: u16) -> Option<u16> {
+        let bytes = self.memory.get(address as usize..)?;
+        let (_, length) = Instruction::decode_slice(bytes).ok()?;
+
+        Some(address.wrapping_add(length as u16))
+    }
+
+    /// Whether `address` is part of a reachable instruction.
+    pub fn is_code(&self, address: u16) -> bool {
+        self.code.get(address as usize).copied().unwrap_or(false)
+    }
+}
+
+/// Something suspicious found in reachable code by `lint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// An illegal opcode is reachable and would lock up the CPU.
+    IllegalOpcode(u8),
+    /// A return is reachable from the cartridge entry point without going through a call, so it
+    /// would pop whatever happens to be on the stack.
+    ReturnWithoutCall,
+    /// Code runs in 0xFE00-0xFEFF, which is OAM and unusable memory rather than anything
+    /// executable.
+    CodeInOamOrUnusableMemory,
+}
+
+const CARTRIDGE_ENTRY_POINT: u16 = 0x0100;
+
+/// Flags suspicious patterns in the code `disassembler` has found so far, ordered by address.
+pub fn lint(disassembler: &Disassembler) -> Vec<(u16, Diagnostic)> {
+    let mut diagnostics = Vec::new();
+
+    for (&address, instruction) in disassembler.instructions() {
+        if let Instruction::IllegalOpcode { opcode } = instruction {
+            diagnostics.push((address, Diagnostic::IllegalOpcode(*opcode)));
+        }
+
+        if (0xFE00..=0xFEFF).contains(&address) {
+            diagnostics.push((address, Diagnostic::CodeInOamOrUnusableMemory));
+        }
+    }
+
+    // Walk the entry point's own stack frame, stepping over calls instead of into them.
+    let mut visited = HashSet::new();
+    let mut pending = vec![CARTRIDGE_ENTRY_POINT];
+
+    while let Some(address) = pending.pop() {
+        let instruction = match disassembler.instructions().get(&address) {
+            Some(instruction) if visited.insert(address) => instruction,
+            _ => continue,
+        };
+
+        match instruction {
+            Instruction::Return
+            | Instruction::ConditionalReturn { .. }
+            | Instruction::ReturnAfterInterrupt => {
+                diagnostics.push((address, Diagnostic::ReturnWithoutCall))
+            }
+            Instruction::Call { .. }
+            | Instruction::ConditionalCall { .. }
+            | Instruction::Reset { .. } => {}
+            _ => pending.extend(instruction.branch_target(address)),
+        }
+
+        if !instruction.is_terminator() {
+            pending.extend(disassembler.next_instruction_address(address));
+        }
+    }
+
+    diagnostics.sort_by_key(|(address, _)| *address);
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_switchable_bank() {
+        let mut rom = vec![0x00; BANK_SIZE * 3];
+
+        rom[BANK_SIZE * 2..BANK_SIZE * 2 + 3].copy_from_slice(&[0xC3, 0x00, 0x40]);
+        rom[BANK_SIZE * 2 + 3..BANK_SIZE * 2 + 6].copy_from_slice(&[0xCD, 0x50, 0x01]);
+
+        let lines = disassemble(&rom, 2);
+
+        assert_eq!(lines.len(), BANK_SIZE - 4);
+        assert_eq!(lines[0].to_string(), "02:4000 JP $4000");
+        assert_eq!(lines[0].target, Some(BankedAddress::new(2, 0x4000)));
+        assert_eq!(lines[0].target.unwrap().to_string(), "02:4000");
+        assert_eq!(lines[1].to_string(), "02:4003 CALL $0150");
+        assert_eq!(lines[1].target.unwrap().to_string(), "00:0150");
+        assert_eq!(lines[2].to_string(), "02:4006 NOP");
+    }
+
+    #[test]
+    fn test_disassemble_home_bank() {
+        let lines = disassemble(&[0x18, 0xFE, 0xD3], 0);
+
+        assert_eq!(lines[0].to_string(), "00:0000 JR -2");
+        assert_eq!(lines[0].target.unwrap().to_string(), "00:0000");
+        assert_eq!(lines[1].to_string(), "00:0002 DB $D3");
+        assert_eq!(
+            lines[1].instruction,
+            Some(Instruction::IllegalOpcode { opcode: 0xD3 })
+        );
+    }
+
+    #[test]
+    fn test_switchable_target_from_home_bank_is_unknown() {
+        let lines = disassemble(&[0xCD, 0x00, 0x40, 0xC3, 0x00, 0xC0], 0);
+
+        assert_eq!(lines[0].target.unwrap().bank, None);
+        assert_eq!(lines[0].target.unwrap().to_string(), "??:4000");
+        assert_eq!(lines[1].target.unwrap().to_string(), "C000");
+    }
+
+    #[test]
+    fn test_banks_beyond_0xff() {
+        let mut rom = vec![0x00; BANK_SIZE * 0x101];
+
+        rom[BANK_SIZE * 0x100..BANK_SIZE * 0x100 + 3].copy_from_slice(&[0xC3, 0x10, 0x40]);
+
+        let lines = disassemble(&rom, 0x100);
+
+        assert_eq!(lines[0].to_string(), "100:4000 JP $4010");
+        assert_eq!(lines[0].target.unwrap().bank, Some(0x100));
+    }
+
+    #[test]
+    fn test_recursive_traversal_skips_inline_data() {
+        let mut memory = vec![0xD9; 0x0100];
+
+        // JP $0110, a data table, then INC A followed by a loop calling into it.
+        memory.extend_from_slice(&[0xC3, 0x10, 0x01]);
+        memory.extend_from_slice(&[0xD3, 0x3C, 0x01, 0xFF, 0x10, 0x20, 0x30, 0x40]);
+        memory.extend_from_slice(&[0xAA; 5]);
+        memory.extend_from_slice(&[0x3C, 0xCD, 0x20, 0x01, 0x18, 0xFA]);
+        memory.resize(0x0120, 0x00);
+        memory.extend_from_slice(&[0x3D, 0xC9]);
+
+        let mut disassembler = Disassembler::new(&memory);
+
+        disassembler.run();
+
+        assert!(disassembler.is_code(0x0100));
+        assert!((0x0103..0x0110).all(|address| !disassembler.is_code(address)));
+        assert!((0x0116..0x0120).all(|address| !disassembler.is_code(address)));
+        assert_eq!(
+            disassembler
+                .instructions()
+                .range(0x0100..)
+                .map(|(address, _)| *address)
+                .collect::<Vec<_>>(),
+            vec![0x0100, 0x0110, 0x0111, 0x0114, 0x0120, 0x0121]
+        );
+        assert_eq!(
+            disassembler.instructions()[&0x0048],
+            Instruction::ReturnAfterInterrupt
+        );
+    }
+
+    #[test]
+    fn test_recursive_traversal_from_seeded_entry_point() {
+        let memory = [0xE9, 0x00, 0x00, 0x3C, 0xC9];
+        let mut disassembler = Disassembler::new(&memory);
+
+        disassembler.run();
+
+        assert!(!disassembler.is_code(0x0003));
+
+        disassembler.add_entry_point(0x0003);
+        disassembler.run();
+
+        assert!(disassembler.is_code(0x0003));
+        assert!(disassembler.is_code(0x0004));
+        assert!(!disassembler.is_code(0x0001));
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut memory = vec![0xD9; 0x0100];
+
+        // JP $0108 over a data table holding illegal opcodes, then CALL $0110, JR NZ,$0120 into
+        // an illegal opcode and a RET reached without a call.
+        memory.extend_from_slice(&[0xC3, 0x08, 0x01]);
+        memory.extend_from_slice(&[0xD3, 0xDB, 0x00, 0x00, 0x00]);
+        memory.extend_from_slice(&[0xCD, 0x10, 0x01, 0x20, 0x13, 0xC9, 0x00, 0x00]);
+        memory.extend_from_slice(&[0x3C, 0xC9]);
+        memory.resize(0x0120, 0x00);
+        memory.push(0xED);
+        memory.resize(0xFE00, 0x00);
+        memory.extend_from_slice(&[0x00, 0xC9]);
+
+        let mut disassembler = Disassembler::new(&memory);
+
+        disassembler.add_entry_point(0xFE00);
+        disassembler.run();
+
+        assert_eq!(
+            lint(&disassembler),
+            vec![
+                (0x010D, Diagnostic::ReturnWithoutCall),
+                (0x0120, Diagnostic::IllegalOpcode(0xED)),
+                (0xFE00, Diagnostic::CodeInOamOrUnusableMemory),
+                (0xFE01, Diagnostic::CodeInOamOrUnusableMemory),
+            ]
+        );
+    }
+}
@@ -0,0 +1,785 @@
+use crate::cpu::{Cpu, Flags, Instruction, MathOperation, Register};
+use crate::error::{OniError, Result};
+
+impl Cpu {
+    /// Executes an already decoded instruction against the current state, returning the number of
+    /// cycles it took. Nothing is fetched, so PC only changes if the instruction itself changes it.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<u8> {
+        match instruction {
+            Instruction::NoOperation => Ok(4),
+            Instruction::Halt => {
+                self.halted = true;
+
+                Ok(4)
+            }
+            Instruction::AbsoluteJump { address } => {
+                self.registers.write(Register::PC, address);
+
+                Ok(16)
+            }
+            // PC already points past the two-byte JR, so `JR -2` jumps back onto itself.
+            Instruction::RelativeJump { steps } => {
+                self.jump_relative(steps);
+
+                Ok(12)
+            }
+            Instruction::ConditionalRelativeJump { condition, steps } => {
+                if condition.is_met(&self.registers.flags()) {
+                    self.jump_relative(steps);
+
+                    Ok(12)
+                } else {
+                    Ok(8)
+                }
+            }
+            Instruction::ResetInterruptMasterEnableFlag => {
+                self.ime = false;
+                self.ime_scheduled = false;
+
+                Ok(4)
+            }
+            Instruction::SetInterruptMasterEnableFlag => {
+                self.ime_scheduled = true;
+
+                Ok(4)
+            }
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register1,
+                register2,
+                treat_value_in_first_register_as_memory_address,
+                treat_value_in_second_register_as_memory_address,
+                operation_on_first_register,
+                operation_on_second_register,
+            } => {
+                let value = if treat_value_in_first_register_as_memory_address {
+                    self.read(self.registers.read(register1))
+                } else {
+                    self.registers.read(register1) as u8
+                };
+
+                if treat_value_in_second_register_as_memory_address {
+                    self.write(self.registers.read(register2), value);
+                } else {
+                    self.registers.write(register2, value as u16);
+                }
+
+                self.apply_math_operation(register1, operation_on_first_register);
+                self.apply_math_operation(register2, operation_on_second_register);
+
+                if treat_value_in_first_register_as_memory_address
+                    || treat_value_in_second_register_as_memory_address
+                {
+                    Ok(8)
+                } else {
+                    Ok(4)
+                }
+            }
+            Instruction::IncrementValueInRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => Ok(self.step_value_in_register(
+                register,
+                treat_value_in_register_as_memory_address,
+                MathOperation::Increment,
+            )),
+            Instruction::DecrementValueInRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => Ok(self.step_value_in_register(
+                register,
+                treat_value_in_register_as_memory_address,
+                MathOperation::Decrement,
+            )),
+            Instruction::AddOneByteToAccumulator { value } => {
+                self.add_to_accumulator(value, false);
+
+                Ok(8)
+            }
+            Instruction::AddOneByteAndCarryFlagToAccumulator { value } => {
+                self.add_to_accumulator(value, self.registers.flags().cy);
+
+                Ok(8)
+            }
+            Instruction::AddValueOfRegisterAndCarryFlagToAccumulator {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => {
+                let value = self.operand(register, treat_value_in_register_as_memory_address);
+
+                self.add_to_accumulator(value, self.registers.flags().cy);
+
+                Ok(if treat_value_in_register_as_memory_address {
+                    8
+                } else {
+                    4
+                })
+            }
+            Instruction::SubtractOneByteAndCarryFlagFromAccumulator { value } => {
+                self.subtract_from_accumulator(value, self.registers.flags().cy);
+
+                Ok(8)
+            }
+            Instruction::SubtractValueOfRegisterAndCarryFlagFromAccumulator {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => {
+                let value = self.operand(register, treat_value_in_register_as_memory_address);
+
+                self.subtract_from_accumulator(value, self.registers.flags().cy);
+
+                Ok(if treat_value_in_register_as_memory_address {
+                    8
+                } else {
+                    4
+                })
+            }
+            Instruction::CompareAccumulatorAndRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => {
+                let value = self.operand(register, treat_value_in_register_as_memory_address);
+
+                self.subtract_for_flags(value, false);
+
+                Ok(if treat_value_in_register_as_memory_address {
+                    8
+                } else {
+                    4
+                })
+            }
+            Instruction::CompareAccumulatorAndOneByte { value } => {
+                self.subtract_for_flags(value, false);
+
+                Ok(8)
+            }
+            Instruction::SetCarryFlag => {
+                let mut flags = self.registers.flags();
+
+                flags.n = false;
+                flags.h = false;
+                flags.cy = true;
+
+                self.registers.set_flags(flags);
+
+                Ok(4)
+            }
+            Instruction::NotCarryFlag => {
+                let mut flags = self.registers.flags();
+
+                flags.n = false;
+                flags.h = false;
+                flags.cy = !flags.cy;
+
+                self.registers.set_flags(flags);
+
+                Ok(4)
+            }
+            Instruction::Not { register } => {
+                let value = self.registers.read(register);
+                let mut flags = self.registers.flags();
+
+                self.registers.write(register, !value);
+
+                flags.n = true;
+                flags.h = true;
+
+                self.registers.set_flags(flags);
+
+                Ok(4)
+            }
+            Instruction::StoreStackPointerInMemory { address } => {
+                let [low, high] = self.registers.read(Register::SP).to_le_bytes();
+
+                self.write(address, low);
+                self.write(address.wrapping_add(1), high);
+
+                Ok(20)
+            }
+            Instruction::StoreContentOfRegisterHLInStackPointer => {
+                self.registers
+                    .write(Register::SP, self.registers.read(Register::HL));
+
+                Ok(8)
+            }
+            Instruction::StoreAccumulatorInMemorySpecifiedByRegisterC => {
+                let address = 0xFF00 | self.registers.read(Register::C);
+
+                self.write(address, self.registers.read(Register::A) as u8);
+
+                Ok(8)
+            }
+            Instruction::LoadAccumulatorFromMemorySpecifiedByRegisterC => {
+                let address = 0xFF00 | self.registers.read(Register::C);
+                let value = self.read(address);
+
+                self.registers.write(Register::A, value as u16);
+
+                Ok(8)
+            }
+            _ => Err(OniError::UnimplementedInstruction(instruction)),
+        }
+    }
+
+    /// Executes INC or DEC. The 8-bit forms update Z, N and H, while the 16-bit forms touch no
+    /// flags at all. C is left alone by both.
+    fn step_value_in_register(
+        &mut self,
+        register: Register,
+        treat_value_in_register_as_memory_address: bool,
+        operation: MathOperation,
+    ) -> u8 {
+        if !treat_value_in_register_as_memory_address && register.width_in_bits() == 16 {
+            self.apply_math_operation(register, Some(operation));
+
+            return 8;
+        }
+
+        let value = self.operand(register, treat_value_in_register_as_memory_address);
+        let (result, half_carry) = match operation {
+            MathOperation::Increment => (value.wrapping_add(1), value & 0x0F == 0x0F),
+            MathOperation::Decrement => (value.wrapping_sub(1), value & 0x0F == 0x00),
+        };
+        let mut flags = self.registers.flags();
+
+        flags.z = result == 0;
+        flags.n = operation == MathOperation::Decrement;
+        flags.h = half_carry;
+
+        self.registers.set_flags(flags);
+
+        if treat_value_in_register_as_memory_address {
+            self.write(self.registers.read(register), result);
+
+            12
+        } else {
+            self.registers.write(register, result as u16);
+
+            4
+        }
+    }
+
+    fn jump_relative(&mut self, steps: u8) {
+        let pc = self.registers.read(Register::PC);
+
+        self.registers
+            .write(Register::PC, pc.wrapping_add(steps as i8 as u16));
+    }
+
+    /// Reads an 8-bit register, or the byte it points to.
+    fn operand(
+        &mut self,
+        register: Register,
+        treat_value_in_register_as_memory_address: bool,
+    ) -> u8 {
+        if treat_value_in_register_as_memory_address {
+            self.read(self.registers.read(register))
+        } else {
+            self.registers.read(register) as u8
+        }
+    }
+
+    /// Adds `value` and the incoming carry to A. The carry takes part in both the half-carry and
+    /// the carry, so chained ADCs propagate correctly.
+    fn add_to_accumulator(&mut self, value: u8, carry: bool) {
+        let accumulator = self.registers.read(Register::A) as u8;
+        let carry = carry as u8;
+        let result = accumulator as u16 + value as u16 + carry as u16;
+
+        self.registers.write(Register::A, result as u8 as u16);
+        self.registers.set_flags(Flags {
+            z: result as u8 == 0,
+            n: false,
+            h: (accumulator & 0x0F) + (value & 0x0F) + carry > 0x0F,
+            cy: result > 0xFF,
+        });
+    }
+
+    /// Subtracts `value` and the incoming borrow from A, borrowing from bit 4 for H and from
+    /// bit 8 for C.
+    fn subtract_from_accumulator(&mut self, value: u8, borrow: bool) {
+        let result = self.subtract_for_flags(value, borrow);
+
+        self.registers.write(Register::A, result as u16);
+    }
+
+    /// Sets the flags of subtracting `value` and the incoming borrow from A and returns the
+    /// result without storing it, as CP does.
+    fn subtract_for_flags(&mut self, value: u8, borrow: bool) -> u8 {
+        let accumulator = self.registers.read(Register::A) as u8;
+        let borrow = borrow as u8;
+        let result = accumulator as i16 - value as i16 - borrow as i16;
+
+        self.registers.set_flags(Flags {
+            z: result as u8 == 0,
+            n: true,
+            h: ((accumulator & 0x0F) as i16) - ((value & 0x0F) as i16) - (borrow as i16) < 0,
+            cy: result < 0,
+        });
+
+        result as u8
+    }
+
+    fn apply_math_operation(&mut self, register: Register, operation: Option<MathOperation>) {
+        let value = self.registers.read(register);
+
+        match operation {
+            Some(MathOperation::Increment) => self.registers.write(register, value.wrapping_add(1)),
+            Some(MathOperation::Decrement) => self.registers.write(register, value.wrapping_sub(1)),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{Cpu, Flags, Instruction, Register, StepResult};
+
+    #[test]
+    fn test_absolute_jump() {
+        let mut cpu = Cpu::new(&[0xC3, 0x50, 0x01]);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(16));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0150);
+    }
+
+    #[test]
+    fn test_set_carry_flag() {
+        let mut cpu = Cpu::new(&[0x37]);
+
+        cpu.registers.write(Register::F, 0b11100000);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: false,
+                h: false,
+                cy: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_not_carry_flag() {
+        let mut cpu = Cpu::new(&[0x3F, 0x3F]);
+
+        cpu.registers.write(Register::F, 0b11110000);
+
+        cpu.step().unwrap();
+
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: false,
+                h: false,
+                cy: false,
+            }
+        );
+
+        cpu.step().unwrap();
+
+        assert!(cpu.registers.flags().cy);
+        assert!(cpu.registers.flags().z);
+    }
+
+    #[test]
+    fn test_not() {
+        let mut cpu = Cpu::new(&[0x2F]);
+
+        cpu.registers.write(Register::AF, 0x3580);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::A), 0xCA);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: true,
+                h: true,
+                cy: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_store_stack_pointer_in_memory() {
+        let mut cpu = Cpu::new(&[0x08, 0x00, 0xC0]);
+
+        cpu.registers.write(Register::SP, 0xFFF8);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(20));
+        assert_eq!(cpu.peek(0xC000), 0xF8);
+        assert_eq!(cpu.peek(0xC001), 0xFF);
+    }
+
+    #[test]
+    fn test_store_content_of_register_hl_in_stack_pointer() {
+        let mut cpu = Cpu::new(&[0xF9]);
+        let flags = Flags {
+            z: true,
+            n: false,
+            h: true,
+            cy: false,
+        };
+
+        cpu.registers.write(Register::HL, 0xFFF0);
+        cpu.registers.set_flags(flags);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::SP), 0xFFF0);
+        assert_eq!(cpu.registers.flags(), flags);
+    }
+
+    #[test]
+    fn test_load_value_of_first_register_into_second_register() {
+        let mut cpu = Cpu::new(&[0x41, 0x22, 0x3A, 0x0A]);
+
+        cpu.registers.write(Register::A, 0x12);
+        cpu.registers.write(Register::B, 0x00);
+        cpu.registers.write(Register::C, 0x34);
+        cpu.registers.write(Register::HL, 0xC000);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::B), 0x34);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.peek(0xC000), 0x12);
+        assert_eq!(cpu.registers.read(Register::HL), 0xC001);
+
+        cpu.write(0xC001, 0x56);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::A), 0x56);
+        assert_eq!(cpu.registers.read(Register::HL), 0xC000);
+
+        cpu.write(0x3434, 0x78);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::A), 0x78);
+    }
+
+    #[test]
+    fn test_accumulator_memory_specified_by_register_c() {
+        let mut cpu = Cpu::new(&[0xE2, 0xF2]);
+
+        cpu.registers.write(Register::A, 0x20);
+        cpu.registers.write(Register::C, 0x00);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.peek(0xFF00), 0x20);
+
+        cpu.registers.write(Register::A, 0x00);
+
+        // The upper two bits of P1 always read back as 1.
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::A), 0xE0);
+    }
+
+    #[test]
+    fn test_increment_and_decrement() {
+        let mut cpu = Cpu::new(&[0x34, 0x35, 0x05, 0x23, 0x0B]);
+        let flags = Flags {
+            z: false,
+            n: true,
+            h: false,
+            cy: true,
+        };
+
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.registers.set_flags(flags);
+        cpu.memory[0xC000] = 0x0F;
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(12));
+        assert_eq!(cpu.memory[0xC000], 0x10);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: false,
+                h: true,
+                cy: true,
+            }
+        );
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(12));
+        assert_eq!(cpu.memory[0xC000], 0x0F);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: true,
+                h: true,
+                cy: true,
+            }
+        );
+
+        cpu.registers.write(Register::B, 0x01);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::B), 0x00);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: true,
+                h: false,
+                cy: true,
+            }
+        );
+
+        cpu.registers.set_flags(flags);
+        cpu.registers.write(Register::HL, 0xFFFF);
+        cpu.registers.write(Register::BC, 0x0000);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::HL), 0x0000);
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::BC), 0xFFFF);
+        assert_eq!(cpu.registers.flags(), flags);
+    }
+
+    #[test]
+    fn test_add_one_byte_to_accumulator() {
+        let mut cpu = Cpu::new(&[]);
+
+        cpu.registers.write(Register::A, 0xF0);
+
+        assert_eq!(
+            cpu.execute(Instruction::AddOneByteToAccumulator { value: 0x10 })
+                .unwrap(),
+            8
+        );
+        assert_eq!(cpu.registers.read(Register::A), 0x00);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: false,
+                h: false,
+                cy: true,
+            }
+        );
+        assert_eq!(cpu.registers.read(Register::PC), 0x0000);
+
+        cpu.registers.write(Register::A, 0x0F);
+        cpu.execute(Instruction::AddOneByteToAccumulator { value: 0x01 })
+            .unwrap();
+
+        assert_eq!(cpu.registers.read(Register::A), 0x10);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: false,
+                h: true,
+                cy: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_add_with_carry() {
+        let mut cpu = Cpu::new(&[]);
+
+        cpu.registers.write(Register::A, 0x0F);
+        cpu.registers.set_flags(Flags {
+            z: false,
+            n: false,
+            h: false,
+            cy: true,
+        });
+
+        assert_eq!(
+            cpu.execute(Instruction::AddOneByteAndCarryFlagToAccumulator { value: 0x00 })
+                .unwrap(),
+            8
+        );
+        assert_eq!(cpu.registers.read(Register::A), 0x10);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: false,
+                h: true,
+                cy: false,
+            }
+        );
+
+        // 0x01FF + 0x0001 as two chained 8-bit additions.
+        cpu.registers.write(Register::A, 0xFF);
+        cpu.registers.write(Register::B, 0x01);
+        cpu.execute(Instruction::AddOneByteToAccumulator { value: 0x01 })
+            .unwrap();
+
+        assert_eq!(cpu.registers.read(Register::A), 0x00);
+
+        cpu.registers.write(Register::A, 0x01);
+
+        assert_eq!(
+            cpu.execute(Instruction::AddValueOfRegisterAndCarryFlagToAccumulator {
+                register: Register::B,
+                treat_value_in_register_as_memory_address: false,
+            })
+            .unwrap(),
+            4
+        );
+        assert_eq!(cpu.registers.read(Register::A), 0x03);
+        assert!(!cpu.registers.flags().cy);
+    }
+
+    #[test]
+    fn test_subtract_with_carry() {
+        let mut cpu = Cpu::new(&[]);
+
+        // 0x0100 - 0x0001 as two chained 8-bit subtractions.
+        cpu.registers.write(Register::A, 0x00);
+        cpu.registers.set_flags(Flags {
+            z: false,
+            n: false,
+            h: false,
+            cy: true,
+        });
+
+        cpu.execute(Instruction::SubtractOneByteAndCarryFlagFromAccumulator { value: 0x00 })
+            .unwrap();
+
+        assert_eq!(cpu.registers.read(Register::A), 0xFF);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: true,
+                h: true,
+                cy: true,
+            }
+        );
+
+        cpu.registers.write(Register::A, 0x01);
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.memory[0xC000] = 0x00;
+
+        assert_eq!(
+            cpu.execute(
+                Instruction::SubtractValueOfRegisterAndCarryFlagFromAccumulator {
+                    register: Register::HL,
+                    treat_value_in_register_as_memory_address: true,
+                }
+            )
+            .unwrap(),
+            8
+        );
+        assert_eq!(cpu.registers.read(Register::A), 0x00);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: true,
+                h: false,
+                cy: false,
+            }
+        );
+
+        cpu.registers.write(Register::A, 0x10);
+        cpu.execute(Instruction::SubtractOneByteAndCarryFlagFromAccumulator { value: 0x0F })
+            .unwrap();
+
+        assert_eq!(cpu.registers.read(Register::A), 0x01);
+        assert!(cpu.registers.flags().h);
+        assert!(!cpu.registers.flags().cy);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut cpu = Cpu::new(&[0xFE, 0x2F, 0xB8, 0xBE]);
+
+        cpu.registers.write(Register::A, 0x3C);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::A), 0x3C);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: true,
+                h: true,
+                cy: false,
+            }
+        );
+
+        cpu.registers.write(Register::B, 0x3C);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: true,
+                n: true,
+                h: false,
+                cy: false,
+            }
+        );
+
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.memory[0xC000] = 0x40;
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::A), 0x3C);
+        assert_eq!(
+            cpu.registers.flags(),
+            Flags {
+                z: false,
+                n: true,
+                h: false,
+                cy: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_relative_jump() {
+        let mut program = vec![0x00; 0x0110];
+
+        program[0x0100..0x0102].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut cpu = Cpu::new(&program);
+
+        cpu.registers.write(Register::PC, 0x0100);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(12));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0100);
+
+        program[0x0100..0x0102].copy_from_slice(&[0x18, 0x05]);
+        cpu = Cpu::new(&program);
+        cpu.registers.write(Register::PC, 0x0100);
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.registers.read(Register::PC), 0x0107);
+    }
+
+    #[test]
+    fn test_conditional_relative_jump() {
+        let mut cpu = Cpu::new(&[0x20, 0x05, 0x20, 0xFC]);
+
+        cpu.registers.set_flags(Flags {
+            z: true,
+            n: false,
+            h: false,
+            cy: false,
+        });
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0002);
+
+        cpu.registers.set_flags(Flags {
+            z: false,
+            n: false,
+            h: false,
+            cy: false,
+        });
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(12));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0000);
+    }
+}
@@ -1,5 +1,25 @@
-#[derive(Debug)]
-pub enum Flag {
-    Z,
-    CY,
+/// Decoded view of the F register.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    pub z: bool,
+    pub n: bool,
+    pub h: bool,
+    pub cy: bool,
+}
+
+impl From<u8> for Flags {
+    fn from(value: u8) -> Flags {
+        Flags {
+            z: value & 0b10000000 != 0,
+            n: value & 0b01000000 != 0,
+            h: value & 0b00100000 != 0,
+            cy: value & 0b00010000 != 0,
+        }
+    }
+}
+
+impl From<Flags> for u8 {
+    fn from(flags: Flags) -> u8 {
+        (flags.z as u8) << 7 | (flags.n as u8) << 6 | (flags.h as u8) << 5 | (flags.cy as u8) << 4
+    }
 }
@@ -0,0 +1,158 @@
+use crate::cpu::{DisassembledLine, Instruction, Register};
+use std::fmt::{self, Display, Formatter};
+
+/// A disassembled line along with a description of the idiom it starts, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedLine {
+    pub line: DisassembledLine,
+    pub annotation: Option<String>,
+}
+
+impl Display for AnnotatedLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.annotation {
+            Some(annotation) => write!(f, "{} ; {}", self.line, annotation),
+            None => write!(f, "{}", self.line),
+        }
+    }
+}
+
+/// Annotates common idioms in a linear listing: zeroing and testing A, pointers loaded right
+/// before being dereferenced, and byte copies through `LD A,(HL+)`.
+pub fn annotate_idioms(lines: Vec<DisassembledLine>) -> Vec<AnnotatedLine> {
+    let annotations = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let next = lines
+                .get(index + 1)
+                .and_then(|line| line.instruction.as_ref());
+
+            line.instruction
+                .as_ref()
+                .and_then(|instruction| idiom(instruction, next))
+        })
+        .collect::<Vec<_>>();
+
+    lines
+        .into_iter()
+        .zip(annotations)
+        .map(|(line, annotation)| AnnotatedLine { line, annotation })
+        .collect()
+}
+
+fn idiom(instruction: &Instruction, next: Option<&Instruction>) -> Option<String> {
+    match instruction {
+        Instruction::LogicalXorOnAccumulatorAndRegister {
+            register: Register::A,
+            treat_value_in_register_as_memory_address: false,
+        }
+        | Instruction::SubtractValueOfSecondRegisterFromFirstRegister {
+            register2: Register::A,
+            treat_value_in_second_register_as_memory_address: false,
+            ..
+        } => Some("A = 0".to_string()),
+        Instruction::LogicalOrOnAccumulatorAndRegister {
+            register: Register::A,
+            treat_value_in_register_as_memory_address: false,
+        }
+        | Instruction::LogicalAndOnAccumulatorAndRegister {
+            register: Register::A,
+            treat_value_in_register_as_memory_address: false,
+        } => Some("Z = (A == 0)".to_string()),
+        Instruction::LoadTwoBytesOfDataIntoRegister { data, register }
+            if next.is_some_and(|next| dereferences(next, *register)) =>
+        {
+            Some(format!("{} points to ${:04X}", register, data))
+        }
+        Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+            register1: Register::HL,
+            register2: Register::A,
+            treat_value_in_first_register_as_memory_address: true,
+            ..
+        } => match next {
+            Some(Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register1: Register::A,
+                register2,
+                treat_value_in_second_register_as_memory_address: true,
+                ..
+            }) => Some(format!("copies (HL) to ({})", register2)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `instruction` reads or writes memory through `register`.
+fn dereferences(instruction: &Instruction, register: Register) -> bool {
+    match *instruction {
+        Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+            register1,
+            register2,
+            treat_value_in_first_register_as_memory_address,
+            treat_value_in_second_register_as_memory_address,
+            ..
+        } => {
+            (treat_value_in_first_register_as_memory_address && register1 == register)
+                || (treat_value_in_second_register_as_memory_address && register2 == register)
+        }
+        Instruction::LoadOneByteOfDataIntoRegister {
+            register: target,
+            treat_value_in_register_as_memory_address,
+            ..
+        }
+        | Instruction::IncrementValueInRegister {
+            register: target,
+            treat_value_in_register_as_memory_address,
+        }
+        | Instruction::DecrementValueInRegister {
+            register: target,
+            treat_value_in_register_as_memory_address,
+        } => treat_value_in_register_as_memory_address && target == register,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::disassemble;
+
+    fn listing(bytes: &[u8]) -> Vec<String> {
+        annotate_idioms(disassemble(bytes, 0))
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_zero_idiom() {
+        assert_eq!(
+            listing(&[0xAF, 0x97, 0xB7, 0xA8]),
+            vec![
+                "00:0000 XOR A ; A = 0",
+                "00:0001 SUB A ; A = 0",
+                "00:0002 OR A ; Z = (A == 0)",
+                "00:0003 XOR B",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_and_copy_idioms() {
+        assert_eq!(
+            listing(&[
+                0x21, 0x00, 0xC0, 0x36, 0x00, 0x11, 0x00, 0xD0, 0x2A, 0x12, 0x01, 0x00, 0x80, 0x00
+            ]),
+            vec![
+                "00:0000 LD HL,$C000 ; HL points to $C000",
+                "00:0003 LD (HL),$00",
+                "00:0005 LD DE,$D000",
+                "00:0008 LD A,(HL+) ; copies (HL) to (DE)",
+                "00:0009 LD (DE),A",
+                "00:000A LD BC,$8000",
+                "00:000D NOP",
+            ]
+        );
+    }
+}
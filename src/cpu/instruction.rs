@@ -1,18 +1,26 @@
-use crate::cpu::{Flag, Register};
-use byteorder::{BigEndian, ReadBytesExt};
-use eyre::{eyre, Result};
-use std::io::Cursor;
+use crate::cpu::{Condition, Register};
+use crate::error::{DecodeError, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Cursor, Read};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MathOperation {
     Increment,
     Decrement,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     NoOperation,
-    Stop,
+    /// One of the eleven opcodes with no defined behavior, which lock up the CPU.
+    IllegalOpcode {
+        opcode: u8,
+    },
+    /// STOP is followed by a byte that's normally 0x00. Anything else makes it a corrupted STOP.
+    Stop {
+        operand: u8,
+    },
     Halt,
     Reset {
         location: u8,
@@ -45,12 +53,8 @@ pub enum Instruction {
     AbsoluteJump {
         address: u16,
     },
-    AbsoluteJumpIfFlagIsZero {
-        flag: Flag,
-        address: u16,
-    },
-    AbsoluteJumpIfFlagIsOne {
-        flag: Flag,
+    ConditionalAbsoluteJump {
+        condition: Condition,
         address: u16,
     },
     AbsoluteJumpToAddressInRegister {
@@ -59,31 +63,20 @@ pub enum Instruction {
     RelativeJump {
         steps: u8,
     },
-    RelativeJumpIfFlagIsZero {
-        flag: Flag,
-        steps: u8,
-    },
-    RelativeJumpIfFlagIsOne {
-        flag: Flag,
+    ConditionalRelativeJump {
+        condition: Condition,
         steps: u8,
     },
     Return,
-    ReturnIfFlagIsZero {
-        flag: Flag,
-    },
-    ReturnIfFlagIsOne {
-        flag: Flag,
+    ConditionalReturn {
+        condition: Condition,
     },
     ReturnAfterInterrupt,
     Call {
         address: u16,
     },
-    CallIfFlagIsZero {
-        flag: Flag,
-        address: u16,
-    },
-    CallIfFlagIsOne {
-        flag: Flag,
+    ConditionalCall {
+        condition: Condition,
         address: u16,
     },
     RotateContentOfRegisterAToLeft,
@@ -132,6 +125,10 @@ pub enum Instruction {
     AddOneByteAndCarryFlagToAccumulator {
         value: u8,
     },
+    AddValueOfRegisterAndCarryFlagToAccumulator {
+        register: Register,
+        treat_value_in_register_as_memory_address: bool,
+    },
     SubtractValueOfSecondRegisterFromFirstRegister {
         register1: Register,
         register2: Register,
@@ -143,6 +140,10 @@ pub enum Instruction {
     SubtractOneByteAndCarryFlagFromAccumulator {
         value: u8,
     },
+    SubtractValueOfRegisterAndCarryFlagFromAccumulator {
+        register: Register,
+        treat_value_in_register_as_memory_address: bool,
+    },
     LogicalAndOnAccumulatorAndRegister {
         register: Register,
         treat_value_in_register_as_memory_address: bool,
@@ -191,8 +192,12 @@ pub enum Instruction {
         address: u16,
     },
     StoreContentOfRegisterHLInStackPointer,
-    AddValueToStackPointer,
-    AddValueToStackPointerAndStoreResultInRegisterHL,
+    AddValueToStackPointer {
+        value: u8,
+    },
+    AddValueToStackPointerAndStoreResultInRegisterHL {
+        value: u8,
+    },
     SwapLowerBytesWithHigherBytesInRegister {
         register: Register,
         treat_value_in_register_as_memory_address: bool,
@@ -214,16 +219,99 @@ pub enum Instruction {
     },
 }
 
+/// An instruction decoded at a known address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub instruction: Instruction,
+    /// Number of bytes the instruction was decoded from.
+    pub length: u16,
+    /// `Instruction::branch_target` resolved against `address`.
+    pub branch_target: Option<u16>,
+}
+
+/// Which `Instruction` variant an instruction is, without its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstructionKind {
+    NoOperation,
+    IllegalOpcode,
+    Stop,
+    Halt,
+    Reset,
+    LoadOneByteOfDataIntoRegister,
+    LoadTwoBytesOfDataIntoRegister,
+    LoadValueOfFirstRegisterIntoSecondRegister,
+    IncrementValueInRegister,
+    DecrementValueInRegister,
+    AbsoluteJump,
+    ConditionalAbsoluteJump,
+    AbsoluteJumpToAddressInRegister,
+    RelativeJump,
+    ConditionalRelativeJump,
+    Return,
+    ConditionalReturn,
+    ReturnAfterInterrupt,
+    Call,
+    ConditionalCall,
+    RotateContentOfRegisterAToLeft,
+    RotateContentOfRegisterAToLeftThroughCarryFlag,
+    RotateContentOfRegisterAToRight,
+    RotateContentOfRegisterAToRightThroughCarryFlag,
+    RotateContentOfRegisterToLeft,
+    RotateContentOfRegisterToLeftThroughCarryFlag,
+    RotateContentOfRegisterToRight,
+    RotateContentOfRegisterToRightThroughCarryFlag,
+    ShiftContentOfRegisterToLeft,
+    ShiftContentOfRegisterToRight,
+    Not,
+    SetCarryFlag,
+    NotCarryFlag,
+    AdjustAccumulatorToBCDNumber,
+    AddValueOfSecondRegisterToFirstRegister,
+    AddOneByteToAccumulator,
+    AddOneByteAndCarryFlagToAccumulator,
+    AddValueOfRegisterAndCarryFlagToAccumulator,
+    SubtractValueOfSecondRegisterFromFirstRegister,
+    SubtractOneByteFromAccumulator,
+    SubtractOneByteAndCarryFlagFromAccumulator,
+    SubtractValueOfRegisterAndCarryFlagFromAccumulator,
+    LogicalAndOnAccumulatorAndRegister,
+    LogicalAndOnAccumulatorAndOneByte,
+    LogicalOrOnAccumulatorAndRegister,
+    LogicalOrOnAccumulatorAndOneByte,
+    LogicalXorOnAccumulatorAndRegister,
+    LogicalXorOnAccumulatorAndOneByte,
+    CompareAccumulatorAndRegister,
+    CompareAccumulatorAndOneByte,
+    PushValueOfRegisterOntoStack,
+    PopValueFromStackIntoRegister,
+    ResetInterruptMasterEnableFlag,
+    SetInterruptMasterEnableFlag,
+    StoreAccumulatorInMemory,
+    LoadAccumulatorFromMemory,
+    StoreAccumulatorInMemorySpecifiedByRegisterC,
+    LoadAccumulatorFromMemorySpecifiedByRegisterC,
+    StoreStackPointerInMemory,
+    StoreContentOfRegisterHLInStackPointer,
+    AddValueToStackPointer,
+    AddValueToStackPointerAndStoreResultInRegisterHL,
+    SwapLowerBytesWithHigherBytesInRegister,
+    CopyNthBitOfRegisterToZFlag,
+    ResetNthBitOfRegister,
+    SetNthBitOfRegister,
+}
+
 impl Instruction {
-    pub fn decode(memory: &mut Cursor<Vec<u8>>) -> Result<Instruction> {
+    pub fn decode<R: Read>(memory: &mut R) -> Result<Instruction> {
         let opcode = memory.read_u8()?;
 
         match opcode {
             0x00 => Ok(Instruction::NoOperation),
-            0x10 => {
-                memory.read_u8()?;
-                Ok(Instruction::Stop)
-            }
+            0x10 => Ok(Instruction::Stop {
+                operand: memory.read_u8()?,
+            }),
+            // 0x76 sits where LD (HL),(HL) would be in the load block below, which is why that
+            // block skips it.
             0x76 => Ok(Instruction::Halt),
 
             0xC7 | 0xD7 | 0xE7 | 0xF7 => Ok(Instruction::Reset {
@@ -245,7 +333,7 @@ impl Instruction {
 
             0x02 | 0x12 => Ok(Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
                 register1: Register::A,
-                register2: match opcode & 0b11110000 {
+                register2: match opcode >> 4 {
                     0x0 => Register::BC,
                     0x1 => Register::DE,
                     _ => unreachable!(),
@@ -262,7 +350,7 @@ impl Instruction {
                 treat_value_in_first_register_as_memory_address: false,
                 treat_value_in_second_register_as_memory_address: true,
                 operation_on_first_register: None,
-                operation_on_second_register: match opcode & 0b11110000 {
+                operation_on_second_register: match opcode >> 4 {
                     0x2 => Some(MathOperation::Increment),
                     0x3 => Some(MathOperation::Decrement),
                     _ => unreachable!(),
@@ -270,25 +358,12 @@ impl Instruction {
             }),
 
             0xC3 => Ok(Instruction::AbsoluteJump {
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-
-            0xC2 => Ok(Instruction::AbsoluteJumpIfFlagIsZero {
-                flag: Flag::Z,
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-            0xD2 => Ok(Instruction::AbsoluteJumpIfFlagIsZero {
-                flag: Flag::CY,
-                address: memory.read_u16::<BigEndian>()?,
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
-            0xCA => Ok(Instruction::AbsoluteJumpIfFlagIsOne {
-                flag: Flag::Z,
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-            0xDA => Ok(Instruction::AbsoluteJumpIfFlagIsOne {
-                flag: Flag::CY,
-                address: memory.read_u16::<BigEndian>()?,
+            0xC2 | 0xCA | 0xD2 | 0xDA => Ok(Instruction::ConditionalAbsoluteJump {
+                condition: condition(opcode),
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
             0xE9 => Ok(Instruction::AbsoluteJumpToAddressInRegister {
@@ -299,52 +374,24 @@ impl Instruction {
                 steps: memory.read_u8()?,
             }),
 
-            0x20 => Ok(Instruction::RelativeJumpIfFlagIsZero {
-                flag: Flag::Z,
-                steps: memory.read_u8()?,
-            }),
-            0x30 => Ok(Instruction::RelativeJumpIfFlagIsZero {
-                flag: Flag::CY,
-                steps: memory.read_u8()?,
-            }),
-
-            0x28 => Ok(Instruction::RelativeJumpIfFlagIsOne {
-                flag: Flag::Z,
-                steps: memory.read_u8()?,
-            }),
-            0x38 => Ok(Instruction::RelativeJumpIfFlagIsOne {
-                flag: Flag::CY,
+            0x20 | 0x28 | 0x30 | 0x38 => Ok(Instruction::ConditionalRelativeJump {
+                condition: condition(opcode),
                 steps: memory.read_u8()?,
             }),
 
             0xC9 => Ok(Instruction::Return),
-            0xC0 => Ok(Instruction::ReturnIfFlagIsZero { flag: Flag::Z }),
-            0xD0 => Ok(Instruction::ReturnIfFlagIsZero { flag: Flag::CY }),
+            0xC0 | 0xC8 | 0xD0 | 0xD8 => Ok(Instruction::ConditionalReturn {
+                condition: condition(opcode),
+            }),
             0xD9 => Ok(Instruction::ReturnAfterInterrupt),
 
-            0xC8 => Ok(Instruction::ReturnIfFlagIsOne { flag: Flag::Z }),
-            0xD8 => Ok(Instruction::ReturnIfFlagIsOne { flag: Flag::CY }),
-
             0xCD => Ok(Instruction::Call {
-                address: memory.read_u16::<BigEndian>()?,
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
-            0xC4 => Ok(Instruction::CallIfFlagIsZero {
-                flag: Flag::Z,
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-            0xD4 => Ok(Instruction::CallIfFlagIsZero {
-                flag: Flag::CY,
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-
-            0xCC => Ok(Instruction::CallIfFlagIsOne {
-                flag: Flag::Z,
-                address: memory.read_u16::<BigEndian>()?,
-            }),
-            0xDC => Ok(Instruction::CallIfFlagIsOne {
-                flag: Flag::CY,
-                address: memory.read_u16::<BigEndian>()?,
+            0xC4 | 0xCC | 0xD4 | 0xDC => Ok(Instruction::ConditionalCall {
+                condition: condition(opcode),
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
             0x2F => Ok(Instruction::Not {
@@ -445,7 +492,7 @@ impl Instruction {
             }),
 
             0x01 | 0x11 | 0x21 | 0x31 => Ok(Instruction::LoadTwoBytesOfDataIntoRegister {
-                data: memory.read_u16::<BigEndian>()?,
+                data: memory.read_u16::<LittleEndian>()?,
                 register: match opcode >> 4 {
                     0x0 => Register::BC,
                     0x1 => Register::DE,
@@ -488,7 +535,7 @@ impl Instruction {
                     treat_value_in_first_register_as_memory_address: opcode & 0b00001111 == 0x6
                         || opcode & 0b00001111 == 0xE,
                     treat_value_in_second_register_as_memory_address: opcode >> 4 == 0x7
-                        && opcode & 0b00001111 < 0xE,
+                        && opcode & 0b00001111 < 0x8,
                     operation_on_first_register: None,
                     operation_on_second_register: None,
                 })
@@ -528,7 +575,8 @@ impl Instruction {
             }),
 
             0x80..=0x87 => Ok(Instruction::AddValueOfSecondRegisterToFirstRegister {
-                register1: match opcode & 0b00001111 {
+                register1: Register::A,
+                register2: match opcode & 0b00001111 {
                     0x0 => Register::B,
                     0x1 => Register::C,
                     0x2 => Register::D,
@@ -539,7 +587,6 @@ impl Instruction {
                     0x7 => Register::A,
                     _ => unreachable!(),
                 },
-                register2: Register::A,
                 treat_value_in_second_register_as_memory_address: opcode == 0x86,
             }),
 
@@ -551,9 +598,25 @@ impl Instruction {
                 value: memory.read_u8()?,
             }),
 
+            0x88..=0x8F => Ok(Instruction::AddValueOfRegisterAndCarryFlagToAccumulator {
+                register: match opcode & 0b00000111 {
+                    0x0 => Register::B,
+                    0x1 => Register::C,
+                    0x2 => Register::D,
+                    0x3 => Register::E,
+                    0x4 => Register::H,
+                    0x5 => Register::L,
+                    0x6 => Register::HL,
+                    0x7 => Register::A,
+                    _ => unreachable!(),
+                },
+                treat_value_in_register_as_memory_address: opcode & 0b00000111 == 0x6,
+            }),
+
             0x90..=0x97 => Ok(
                 Instruction::SubtractValueOfSecondRegisterFromFirstRegister {
-                    register1: match opcode & 0b00001111 {
+                    register1: Register::A,
+                    register2: match opcode & 0b00001111 {
                         0x0 => Register::B,
                         0x1 => Register::C,
                         0x2 => Register::D,
@@ -564,7 +627,6 @@ impl Instruction {
                         0x7 => Register::A,
                         _ => unreachable!(),
                     },
-                    register2: Register::A,
                     treat_value_in_second_register_as_memory_address: opcode == 0x96,
                 },
             ),
@@ -577,6 +639,23 @@ impl Instruction {
                 value: memory.read_u8()?,
             }),
 
+            0x98..=0x9F => Ok(
+                Instruction::SubtractValueOfRegisterAndCarryFlagFromAccumulator {
+                    register: match opcode & 0b00000111 {
+                        0x0 => Register::B,
+                        0x1 => Register::C,
+                        0x2 => Register::D,
+                        0x3 => Register::E,
+                        0x4 => Register::H,
+                        0x5 => Register::L,
+                        0x6 => Register::HL,
+                        0x7 => Register::A,
+                        _ => unreachable!(),
+                    },
+                    treat_value_in_register_as_memory_address: opcode & 0b00000111 == 0x6,
+                },
+            ),
+
             0xA0..=0xA7 => Ok(Instruction::LogicalAndOnAccumulatorAndRegister {
                 register: match opcode & 0b00001111 {
                     0x0 => Register::B,
@@ -677,14 +756,14 @@ impl Instruction {
                 address: (0xFF << 8) | (memory.read_u8()? as u16),
             }),
             0xEA => Ok(Instruction::StoreAccumulatorInMemory {
-                address: memory.read_u16::<BigEndian>()?,
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
             0xF0 => Ok(Instruction::LoadAccumulatorFromMemory {
                 address: (0xFF << 8) | (memory.read_u8()? as u16),
             }),
             0xFA => Ok(Instruction::LoadAccumulatorFromMemory {
-                address: memory.read_u16::<BigEndian>()?,
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
             0xE2 => Ok(Instruction::StoreAccumulatorInMemorySpecifiedByRegisterC),
@@ -692,21 +771,27 @@ impl Instruction {
             0xF2 => Ok(Instruction::LoadAccumulatorFromMemorySpecifiedByRegisterC),
 
             0x08 => Ok(Instruction::StoreStackPointerInMemory {
-                address: memory.read_u16::<BigEndian>()?,
+                address: memory.read_u16::<LittleEndian>()?,
             }),
 
             0xF9 => Ok(Instruction::StoreContentOfRegisterHLInStackPointer),
 
-            0xE8 => Ok(Instruction::AddValueToStackPointer),
+            0xE8 => Ok(Instruction::AddValueToStackPointer {
+                value: memory.read_u8()?,
+            }),
 
-            0xF8 => Ok(Instruction::AddValueToStackPointerAndStoreResultInRegisterHL),
+            0xF8 => Ok(
+                Instruction::AddValueToStackPointerAndStoreResultInRegisterHL {
+                    value: memory.read_u8()?,
+                },
+            ),
 
             0xCB => {
                 let opcode = memory.read_u8()?;
 
                 match opcode {
-                    0x00..=0x07 => Ok(Instruction::RotateContentOfRegisterToLeftThroughCarryFlag {
-                        register: match opcode & 0b00001111 {
+                    0x00..=0x07 => Ok(Instruction::RotateContentOfRegisterToLeft {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -719,24 +804,8 @@ impl Instruction {
                         },
                         treat_value_in_register_as_memory_address: opcode == 0x06,
                     }),
-                    0x08..=0x0F => Ok(
-                        Instruction::RotateContentOfRegisterToRightThroughCarryFlag {
-                            register: match opcode & 0b00001111 {
-                                0x0 => Register::B,
-                                0x1 => Register::C,
-                                0x2 => Register::D,
-                                0x3 => Register::E,
-                                0x4 => Register::H,
-                                0x5 => Register::L,
-                                0x6 => Register::HL,
-                                0x7 => Register::A,
-                                _ => unreachable!(),
-                            },
-                            treat_value_in_register_as_memory_address: opcode == 0x0E,
-                        },
-                    ),
-                    0x10..=0x17 => Ok(Instruction::RotateContentOfRegisterToLeft {
-                        register: match opcode & 0b00001111 {
+                    0x08..=0x0F => Ok(Instruction::RotateContentOfRegisterToRight {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -747,10 +816,10 @@ impl Instruction {
                             0x7 => Register::A,
                             _ => unreachable!(),
                         },
-                        treat_value_in_register_as_memory_address: opcode == 0x16,
+                        treat_value_in_register_as_memory_address: opcode == 0x0E,
                     }),
-                    0x18..=0x1F => Ok(Instruction::RotateContentOfRegisterToRight {
-                        register: match opcode & 0b00001111 {
+                    0x10..=0x17 => Ok(Instruction::RotateContentOfRegisterToLeftThroughCarryFlag {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -761,10 +830,26 @@ impl Instruction {
                             0x7 => Register::A,
                             _ => unreachable!(),
                         },
-                        treat_value_in_register_as_memory_address: opcode == 0x1E,
+                        treat_value_in_register_as_memory_address: opcode == 0x16,
                     }),
+                    0x18..=0x1F => Ok(
+                        Instruction::RotateContentOfRegisterToRightThroughCarryFlag {
+                            register: match opcode & 0b00000111 {
+                                0x0 => Register::B,
+                                0x1 => Register::C,
+                                0x2 => Register::D,
+                                0x3 => Register::E,
+                                0x4 => Register::H,
+                                0x5 => Register::L,
+                                0x6 => Register::HL,
+                                0x7 => Register::A,
+                                _ => unreachable!(),
+                            },
+                            treat_value_in_register_as_memory_address: opcode == 0x1E,
+                        },
+                    ),
                     0x20..=0x27 => Ok(Instruction::ShiftContentOfRegisterToLeft {
-                        register: match opcode & 0b00001111 {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -778,7 +863,7 @@ impl Instruction {
                         treat_value_in_register_as_memory_address: opcode == 0x26,
                     }),
                     0x28..=0x2F => Ok(Instruction::ShiftContentOfRegisterToRight {
-                        register: match opcode & 0b00001111 {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -793,7 +878,7 @@ impl Instruction {
                         reset_first_bit: false,
                     }),
                     0x30..=0x37 => Ok(Instruction::SwapLowerBytesWithHigherBytesInRegister {
-                        register: match opcode & 0b00001111 {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -807,7 +892,7 @@ impl Instruction {
                         treat_value_in_register_as_memory_address: opcode == 0x36,
                     }),
                     0x38..=0x3F => Ok(Instruction::ShiftContentOfRegisterToRight {
-                        register: match opcode & 0b00001111 {
+                        register: match opcode & 0b00000111 {
                             0x0 => Register::B,
                             0x1 => Register::C,
                             0x2 => Register::D,
@@ -824,7 +909,7 @@ impl Instruction {
                     0x40..=0x47 | 0x50..=0x57 | 0x60..=0x67 | 0x70..=0x77 => {
                         Ok(Instruction::CopyNthBitOfRegisterToZFlag {
                             nth: ((opcode >> 4) - 0x4) * 2,
-                            register: match opcode & 0b00001111 {
+                            register: match opcode & 0b00000111 {
                                 0x0 => Register::B,
                                 0x1 => Register::C,
                                 0x2 => Register::D,
@@ -858,7 +943,7 @@ impl Instruction {
                     0x80..=0x87 | 0x90..=0x97 | 0xA0..=0xA7 | 0xB0..=0xB7 => {
                         Ok(Instruction::ResetNthBitOfRegister {
                             nth: ((opcode >> 4) - 0x8) * 2,
-                            register: match opcode & 0b00001111 {
+                            register: match opcode & 0b00000111 {
                                 0x0 => Register::B,
                                 0x1 => Register::C,
                                 0x2 => Register::D,
@@ -872,7 +957,7 @@ impl Instruction {
                             treat_value_in_register_as_memory_address: (opcode & 0b00001111) == 0x6,
                         })
                     }
-                    0x88..=0x8F | 0x90..=0x9F | 0xA8..=0xAF | 0xB8..=0xBF => {
+                    0x88..=0x8F | 0x98..=0x9F | 0xA8..=0xAF | 0xB8..=0xBF => {
                         Ok(Instruction::ResetNthBitOfRegister {
                             nth: (((opcode >> 4) - 0x8) * 2) + 1,
                             register: match opcode & 0b00001111 {
@@ -892,7 +977,7 @@ impl Instruction {
                     0xC0..=0xC7 | 0xD0..=0xD7 | 0xE0..=0xE7 | 0xF0..=0xF7 => {
                         Ok(Instruction::SetNthBitOfRegister {
                             nth: ((opcode >> 4) - 0xC) * 2,
-                            register: match opcode & 0b00001111 {
+                            register: match opcode & 0b00000111 {
                                 0x0 => Register::B,
                                 0x1 => Register::C,
                                 0x2 => Register::D,
@@ -925,28 +1010,1026 @@ impl Instruction {
                     }
                 }
             }
-            _ => Err(eyre!("Unknown 8 bit opcode")),
+            0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
+                Ok(Instruction::IllegalOpcode { opcode })
+            }
+        }
+    }
+
+    /// Like `decode`, but rejects illegal opcodes instead of decoding them.
+    pub fn decode_strict<R: Read>(memory: &mut R) -> Result<Instruction> {
+        match Instruction::decode(memory)? {
+            Instruction::IllegalOpcode { opcode } => Err(DecodeError::IllegalOpcode(opcode).into()),
+            instruction => Ok(instruction),
+        }
+    }
+
+    /// Decodes an instruction located at `address`, resolving its branch target.
+    pub fn decode_at<R: Read>(reader: &mut R, address: u16) -> Result<DecodedInstruction> {
+        let (instruction, bytes) = Instruction::decode_with_bytes(reader)?;
+        let branch_target = instruction.branch_target(address);
+
+        Ok(DecodedInstruction {
+            address,
+            instruction,
+            length: bytes.len() as u16,
+            branch_target,
+        })
+    }
+
+    /// Decodes the instruction at the start of `bytes`, returning it along with the number of
+    /// bytes it was decoded from.
+    pub fn decode_slice(bytes: &[u8]) -> Result<(Instruction, usize)> {
+        let mut remaining = bytes;
+        let instruction = Instruction::decode(&mut remaining)?;
+
+        Ok((instruction, bytes.len() - remaining.len()))
+    }
+
+    /// Decodes an instruction, also returning the opcode and operand bytes it was decoded from.
+    pub fn decode_with_bytes<R: Read>(reader: &mut R) -> Result<(Instruction, Vec<u8>)> {
+        let mut reader = RecordingReader {
+            reader,
+            bytes: Vec::with_capacity(3),
+        };
+        let instruction = Instruction::decode(&mut reader)?;
+
+        Ok((instruction, reader.bytes))
+    }
+
+    pub fn kind(&self) -> InstructionKind {
+        match self {
+            Instruction::NoOperation => InstructionKind::NoOperation,
+            Instruction::IllegalOpcode { .. } => InstructionKind::IllegalOpcode,
+            Instruction::Stop { .. } => InstructionKind::Stop,
+            Instruction::Halt => InstructionKind::Halt,
+            Instruction::Reset { .. } => InstructionKind::Reset,
+            Instruction::LoadOneByteOfDataIntoRegister { .. } => {
+                InstructionKind::LoadOneByteOfDataIntoRegister
+            }
+            Instruction::LoadTwoBytesOfDataIntoRegister { .. } => {
+                InstructionKind::LoadTwoBytesOfDataIntoRegister
+            }
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister { .. } => {
+                InstructionKind::LoadValueOfFirstRegisterIntoSecondRegister
+            }
+            Instruction::IncrementValueInRegister { .. } => {
+                InstructionKind::IncrementValueInRegister
+            }
+            Instruction::DecrementValueInRegister { .. } => {
+                InstructionKind::DecrementValueInRegister
+            }
+            Instruction::AbsoluteJump { .. } => InstructionKind::AbsoluteJump,
+            Instruction::ConditionalAbsoluteJump { .. } => InstructionKind::ConditionalAbsoluteJump,
+            Instruction::AbsoluteJumpToAddressInRegister { .. } => {
+                InstructionKind::AbsoluteJumpToAddressInRegister
+            }
+            Instruction::RelativeJump { .. } => InstructionKind::RelativeJump,
+            Instruction::ConditionalRelativeJump { .. } => InstructionKind::ConditionalRelativeJump,
+            Instruction::Return => InstructionKind::Return,
+            Instruction::ConditionalReturn { .. } => InstructionKind::ConditionalReturn,
+            Instruction::ReturnAfterInterrupt => InstructionKind::ReturnAfterInterrupt,
+            Instruction::Call { .. } => InstructionKind::Call,
+            Instruction::ConditionalCall { .. } => InstructionKind::ConditionalCall,
+            Instruction::RotateContentOfRegisterAToLeft => {
+                InstructionKind::RotateContentOfRegisterAToLeft
+            }
+            Instruction::RotateContentOfRegisterAToLeftThroughCarryFlag => {
+                InstructionKind::RotateContentOfRegisterAToLeftThroughCarryFlag
+            }
+            Instruction::RotateContentOfRegisterAToRight => {
+                InstructionKind::RotateContentOfRegisterAToRight
+            }
+            Instruction::RotateContentOfRegisterAToRightThroughCarryFlag => {
+                InstructionKind::RotateContentOfRegisterAToRightThroughCarryFlag
+            }
+            Instruction::RotateContentOfRegisterToLeft { .. } => {
+                InstructionKind::RotateContentOfRegisterToLeft
+            }
+            Instruction::RotateContentOfRegisterToLeftThroughCarryFlag { .. } => {
+                InstructionKind::RotateContentOfRegisterToLeftThroughCarryFlag
+            }
+            Instruction::RotateContentOfRegisterToRight { .. } => {
+                InstructionKind::RotateContentOfRegisterToRight
+            }
+            Instruction::RotateContentOfRegisterToRightThroughCarryFlag { .. } => {
+                InstructionKind::RotateContentOfRegisterToRightThroughCarryFlag
+            }
+            Instruction::ShiftContentOfRegisterToLeft { .. } => {
+                InstructionKind::ShiftContentOfRegisterToLeft
+            }
+            Instruction::ShiftContentOfRegisterToRight { .. } => {
+                InstructionKind::ShiftContentOfRegisterToRight
+            }
+            Instruction::Not { .. } => InstructionKind::Not,
+            Instruction::SetCarryFlag => InstructionKind::SetCarryFlag,
+            Instruction::NotCarryFlag => InstructionKind::NotCarryFlag,
+            Instruction::AdjustAccumulatorToBCDNumber => {
+                InstructionKind::AdjustAccumulatorToBCDNumber
+            }
+            Instruction::AddValueOfSecondRegisterToFirstRegister { .. } => {
+                InstructionKind::AddValueOfSecondRegisterToFirstRegister
+            }
+            Instruction::AddOneByteToAccumulator { .. } => InstructionKind::AddOneByteToAccumulator,
+            Instruction::AddOneByteAndCarryFlagToAccumulator { .. } => {
+                InstructionKind::AddOneByteAndCarryFlagToAccumulator
+            }
+            Instruction::AddValueOfRegisterAndCarryFlagToAccumulator { .. } => {
+                InstructionKind::AddValueOfRegisterAndCarryFlagToAccumulator
+            }
+            Instruction::SubtractValueOfSecondRegisterFromFirstRegister { .. } => {
+                InstructionKind::SubtractValueOfSecondRegisterFromFirstRegister
+            }
+            Instruction::SubtractOneByteFromAccumulator { .. } => {
+                InstructionKind::SubtractOneByteFromAccumulator
+            }
+            Instruction::SubtractOneByteAndCarryFlagFromAccumulator { .. } => {
+                InstructionKind::SubtractOneByteAndCarryFlagFromAccumulator
+            }
+            Instruction::SubtractValueOfRegisterAndCarryFlagFromAccumulator { .. } => {
+                InstructionKind::SubtractValueOfRegisterAndCarryFlagFromAccumulator
+            }
+            Instruction::LogicalAndOnAccumulatorAndRegister { .. } => {
+                InstructionKind::LogicalAndOnAccumulatorAndRegister
+            }
+            Instruction::LogicalAndOnAccumulatorAndOneByte { .. } => {
+                InstructionKind::LogicalAndOnAccumulatorAndOneByte
+            }
+            Instruction::LogicalOrOnAccumulatorAndRegister { .. } => {
+                InstructionKind::LogicalOrOnAccumulatorAndRegister
+            }
+            Instruction::LogicalOrOnAccumulatorAndOneByte { .. } => {
+                InstructionKind::LogicalOrOnAccumulatorAndOneByte
+            }
+            Instruction::LogicalXorOnAccumulatorAndRegister { .. } => {
+                InstructionKind::LogicalXorOnAccumulatorAndRegister
+            }
+            Instruction::LogicalXorOnAccumulatorAndOneByte { .. } => {
+                InstructionKind::LogicalXorOnAccumulatorAndOneByte
+            }
+            Instruction::CompareAccumulatorAndRegister { .. } => {
+                InstructionKind::CompareAccumulatorAndRegister
+            }
+            Instruction::CompareAccumulatorAndOneByte { .. } => {
+                InstructionKind::CompareAccumulatorAndOneByte
+            }
+            Instruction::PushValueOfRegisterOntoStack { .. } => {
+                InstructionKind::PushValueOfRegisterOntoStack
+            }
+            Instruction::PopValueFromStackIntoRegister { .. } => {
+                InstructionKind::PopValueFromStackIntoRegister
+            }
+            Instruction::ResetInterruptMasterEnableFlag => {
+                InstructionKind::ResetInterruptMasterEnableFlag
+            }
+            Instruction::SetInterruptMasterEnableFlag => {
+                InstructionKind::SetInterruptMasterEnableFlag
+            }
+            Instruction::StoreAccumulatorInMemory { .. } => {
+                InstructionKind::StoreAccumulatorInMemory
+            }
+            Instruction::LoadAccumulatorFromMemory { .. } => {
+                InstructionKind::LoadAccumulatorFromMemory
+            }
+            Instruction::StoreAccumulatorInMemorySpecifiedByRegisterC => {
+                InstructionKind::StoreAccumulatorInMemorySpecifiedByRegisterC
+            }
+            Instruction::LoadAccumulatorFromMemorySpecifiedByRegisterC => {
+                InstructionKind::LoadAccumulatorFromMemorySpecifiedByRegisterC
+            }
+            Instruction::StoreStackPointerInMemory { .. } => {
+                InstructionKind::StoreStackPointerInMemory
+            }
+            Instruction::StoreContentOfRegisterHLInStackPointer => {
+                InstructionKind::StoreContentOfRegisterHLInStackPointer
+            }
+            Instruction::AddValueToStackPointer { .. } => InstructionKind::AddValueToStackPointer,
+            Instruction::AddValueToStackPointerAndStoreResultInRegisterHL { .. } => {
+                InstructionKind::AddValueToStackPointerAndStoreResultInRegisterHL
+            }
+            Instruction::SwapLowerBytesWithHigherBytesInRegister { .. } => {
+                InstructionKind::SwapLowerBytesWithHigherBytesInRegister
+            }
+            Instruction::CopyNthBitOfRegisterToZFlag { .. } => {
+                InstructionKind::CopyNthBitOfRegisterToZFlag
+            }
+            Instruction::ResetNthBitOfRegister { .. } => InstructionKind::ResetNthBitOfRegister,
+            Instruction::SetNthBitOfRegister { .. } => InstructionKind::SetNthBitOfRegister,
+        }
+    }
+
+    /// Where this instruction jumps, calls or restarts to, given the address it's located at.
+    /// Jumps through a register have no statically known target.
+    pub fn branch_target(&self, address: u16) -> Option<u16> {
+        match self {
+            Instruction::AbsoluteJump { address }
+            | Instruction::ConditionalAbsoluteJump { address, .. }
+            | Instruction::Call { address }
+            | Instruction::ConditionalCall { address, .. } => Some(*address),
+            // Relative jumps are two bytes long and count from the instruction following them.
+            Instruction::RelativeJump { steps }
+            | Instruction::ConditionalRelativeJump { steps, .. } => {
+                Some(address.wrapping_add(2).wrapping_add(*steps as i8 as u16))
+            }
+            Instruction::Reset { location } => Some(*location as u16 * 8),
+            _ => None,
+        }
+    }
+
+    /// Whether execution never continues with the following instruction. Illegal opcodes count,
+    /// since they lock up the CPU.
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Instruction::IllegalOpcode { .. }
+                | Instruction::AbsoluteJump { .. }
+                | Instruction::AbsoluteJumpToAddressInRegister { .. }
+                | Instruction::RelativeJump { .. }
+                | Instruction::Return
+                | Instruction::ReturnAfterInterrupt
+        )
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::NoOperation => write!(f, "NOP"),
+            Instruction::IllegalOpcode { opcode } => write!(f, "DB ${:02X}", opcode),
+            Instruction::Stop { operand: 0x00 } => write!(f, "STOP"),
+            Instruction::Stop { operand } => write!(f, "STOP ${:02X}", operand),
+            Instruction::Halt => write!(f, "HALT"),
+            Instruction::Reset { location } => write!(f, "RST ${:02X}", location * 8),
+            Instruction::LoadOneByteOfDataIntoRegister {
+                data,
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "LD {},${:02X}",
+                operand(*register, *treat_value_in_register_as_memory_address, None),
+                data
+            ),
+            Instruction::LoadTwoBytesOfDataIntoRegister { data, register } => {
+                write!(f, "LD {},${:04X}", register, data)
+            }
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register1,
+                register2,
+                treat_value_in_first_register_as_memory_address,
+                treat_value_in_second_register_as_memory_address,
+                operation_on_first_register,
+                operation_on_second_register,
+            } => write!(
+                f,
+                "LD {},{}",
+                operand(
+                    *register2,
+                    *treat_value_in_second_register_as_memory_address,
+                    *operation_on_second_register
+                ),
+                operand(
+                    *register1,
+                    *treat_value_in_first_register_as_memory_address,
+                    *operation_on_first_register
+                )
+            ),
+            Instruction::IncrementValueInRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "INC {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::DecrementValueInRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "DEC {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::AbsoluteJump { address } => write!(f, "JP ${:04X}", address),
+            Instruction::ConditionalAbsoluteJump { condition, address } => {
+                write!(f, "JP {},${:04X}", condition, address)
+            }
+            Instruction::AbsoluteJumpToAddressInRegister { register } => {
+                write!(f, "JP {}", register)
+            }
+            Instruction::RelativeJump { steps } => write!(f, "JR {:+}", *steps as i8),
+            Instruction::ConditionalRelativeJump { condition, steps } => {
+                write!(f, "JR {},{:+}", condition, *steps as i8)
+            }
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ConditionalReturn { condition } => write!(f, "RET {}", condition),
+            Instruction::ReturnAfterInterrupt => write!(f, "RETI"),
+            Instruction::Call { address } => write!(f, "CALL ${:04X}", address),
+            Instruction::ConditionalCall { condition, address } => {
+                write!(f, "CALL {},${:04X}", condition, address)
+            }
+            Instruction::RotateContentOfRegisterAToLeft => write!(f, "RLCA"),
+            Instruction::RotateContentOfRegisterAToLeftThroughCarryFlag => write!(f, "RLA"),
+            Instruction::RotateContentOfRegisterAToRight => write!(f, "RRCA"),
+            Instruction::RotateContentOfRegisterAToRightThroughCarryFlag => write!(f, "RRA"),
+            Instruction::RotateContentOfRegisterToLeft {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "RLC {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::RotateContentOfRegisterToLeftThroughCarryFlag {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "RL {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::RotateContentOfRegisterToRight {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "RRC {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::RotateContentOfRegisterToRightThroughCarryFlag {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "RR {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::ShiftContentOfRegisterToLeft {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "SLA {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::ShiftContentOfRegisterToRight {
+                register,
+                treat_value_in_register_as_memory_address,
+                reset_first_bit,
+            } => write!(
+                f,
+                "{} {}",
+                if *reset_first_bit { "SRL" } else { "SRA" },
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::Not { .. } => write!(f, "CPL"),
+            Instruction::SetCarryFlag => write!(f, "SCF"),
+            Instruction::NotCarryFlag => write!(f, "CCF"),
+            Instruction::AdjustAccumulatorToBCDNumber => write!(f, "DAA"),
+            Instruction::AddValueOfSecondRegisterToFirstRegister {
+                register1,
+                register2,
+                treat_value_in_second_register_as_memory_address,
+            } => write!(
+                f,
+                "ADD {},{}",
+                register1,
+                operand(
+                    *register2,
+                    *treat_value_in_second_register_as_memory_address,
+                    None
+                )
+            ),
+            Instruction::AddOneByteToAccumulator { value } => write!(f, "ADD A,${:02X}", value),
+            Instruction::AddOneByteAndCarryFlagToAccumulator { value } => {
+                write!(f, "ADC A,${:02X}", value)
+            }
+            Instruction::AddValueOfRegisterAndCarryFlagToAccumulator {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "ADC A,{}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::SubtractValueOfSecondRegisterFromFirstRegister {
+                register2,
+                treat_value_in_second_register_as_memory_address,
+                ..
+            } => write!(
+                f,
+                "SUB {}",
+                operand(
+                    *register2,
+                    *treat_value_in_second_register_as_memory_address,
+                    None
+                )
+            ),
+            Instruction::SubtractOneByteFromAccumulator { value } => {
+                write!(f, "SUB ${:02X}", value)
+            }
+            Instruction::SubtractOneByteAndCarryFlagFromAccumulator { value } => {
+                write!(f, "SBC A,${:02X}", value)
+            }
+            Instruction::SubtractValueOfRegisterAndCarryFlagFromAccumulator {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "SBC A,{}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::LogicalAndOnAccumulatorAndRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "AND {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::LogicalAndOnAccumulatorAndOneByte { value } => {
+                write!(f, "AND ${:02X}", value)
+            }
+            Instruction::LogicalOrOnAccumulatorAndRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "OR {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::LogicalOrOnAccumulatorAndOneByte { value } => {
+                write!(f, "OR ${:02X}", value)
+            }
+            Instruction::LogicalXorOnAccumulatorAndRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "XOR {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::LogicalXorOnAccumulatorAndOneByte { value } => {
+                write!(f, "XOR ${:02X}", value)
+            }
+            Instruction::CompareAccumulatorAndRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "CP {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::CompareAccumulatorAndOneByte { value } => write!(f, "CP ${:02X}", value),
+            Instruction::PushValueOfRegisterOntoStack { register } => {
+                write!(f, "PUSH {}", register)
+            }
+            Instruction::PopValueFromStackIntoRegister { register } => {
+                write!(f, "POP {}", register)
+            }
+            Instruction::ResetInterruptMasterEnableFlag => write!(f, "DI"),
+            Instruction::SetInterruptMasterEnableFlag => write!(f, "EI"),
+            Instruction::StoreAccumulatorInMemory { address } => {
+                write!(f, "LD (${:04X}),A", address)
+            }
+            Instruction::LoadAccumulatorFromMemory { address } => {
+                write!(f, "LD A,(${:04X})", address)
+            }
+            Instruction::StoreAccumulatorInMemorySpecifiedByRegisterC => {
+                write!(f, "LD ($FF00+C),A")
+            }
+            Instruction::LoadAccumulatorFromMemorySpecifiedByRegisterC => {
+                write!(f, "LD A,($FF00+C)")
+            }
+            Instruction::StoreStackPointerInMemory { address } => {
+                write!(f, "LD (${:04X}),SP", address)
+            }
+            Instruction::StoreContentOfRegisterHLInStackPointer => write!(f, "LD SP,HL"),
+            Instruction::AddValueToStackPointer { value } => write!(f, "ADD SP,{:+}", *value as i8),
+            Instruction::AddValueToStackPointerAndStoreResultInRegisterHL { value } => {
+                write!(f, "LD HL,SP{:+}", *value as i8)
+            }
+            Instruction::SwapLowerBytesWithHigherBytesInRegister {
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "SWAP {}",
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::CopyNthBitOfRegisterToZFlag {
+                nth,
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "BIT {},{}",
+                nth,
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::ResetNthBitOfRegister {
+                nth,
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "RES {},{}",
+                nth,
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
+            Instruction::SetNthBitOfRegister {
+                nth,
+                register,
+                treat_value_in_register_as_memory_address,
+            } => write!(
+                f,
+                "SET {},{}",
+                nth,
+                operand(*register, *treat_value_in_register_as_memory_address, None)
+            ),
         }
     }
 }
 
+/// Keeps a copy of everything read through it.
+struct RecordingReader<'a, R> {
+    reader: &'a mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let length = self.reader.read(buffer)?;
+
+        self.bytes.extend_from_slice(&buffer[..length]);
+
+        Ok(length)
+    }
+}
+
+fn operand(
+    register: Register,
+    treat_value_in_register_as_memory_address: bool,
+    operation: Option<MathOperation>,
+) -> String {
+    let suffix = match operation {
+        Some(MathOperation::Increment) => "+",
+        Some(MathOperation::Decrement) => "-",
+        None => "",
+    };
+
+    if treat_value_in_register_as_memory_address {
+        format!("({}{})", register, suffix)
+    } else {
+        format!("{}{}", register, suffix)
+    }
+}
+
+/// Conditional jumps, calls and returns encode their condition in bits 3 and 4 of the opcode.
+fn condition(opcode: u8) -> Condition {
+    match (opcode >> 3) & 0b11 {
+        0b00 => Condition::NZ,
+        0b01 => Condition::Z,
+        0b10 => Condition::NC,
+        0b11 => Condition::C,
+        _ => unreachable!(),
+    }
+}
+
+/// Decodes instructions starting at `start` until a terminator (unconditional jump, return or
+/// illegal opcode, which ends the block as its last instruction) or the end of `bytes` is
+/// reached.
+///
+/// Returns the decoded instructions and the address right after the last one of them. `bytes`
+/// is treated as a 16-bit address space: decoding stops before an instruction that would run
+/// past 0xFFFF, so the returned address always fits in a `u16`.
+pub fn decode_basic_block(bytes: &[u8], start: u16) -> (Vec<Instruction>, u16) {
+    let mut memory = Cursor::new(&bytes[..bytes.len().min(0xFFFF)]);
+    let mut instructions = Vec::new();
+
+    memory.set_position(start as u64);
+
+    loop {
+        let position = memory.position();
+
+        match Instruction::decode(&mut memory) {
+            Ok(instruction) => {
+                let is_terminator = instruction.is_terminator();
+
+                instructions.push(instruction);
+
+                if is_terminator {
+                    break;
+                }
+            }
+            Err(_) => {
+                memory.set_position(position);
+                break;
+            }
+        }
+    }
+
+    (instructions, memory.position() as u16)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::OniError;
 
     #[test]
-    fn test_invalid_opcodes() {
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xD3])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xE3])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xE4])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xF4])).is_err());
+    fn test_illegal_opcodes() {
+        for opcode in [
+            0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+        ] {
+            assert_eq!(
+                Instruction::decode(&mut Cursor::new(vec![opcode])).unwrap(),
+                Instruction::IllegalOpcode { opcode }
+            );
+            assert!(matches!(
+                Instruction::decode_strict(&mut Cursor::new(vec![opcode])),
+                Err(OniError::Decode(DecodeError::IllegalOpcode(illegal))) if illegal == opcode
+            ));
+        }
+
         assert!(Instruction::decode(&mut Cursor::new(vec![0xCB])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xDB])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xEB])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xEC])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xFC])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xDD])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xED])).is_err());
-        assert!(Instruction::decode(&mut Cursor::new(vec![0xFD])).is_err());
+    }
+
+    #[test]
+    fn test_every_other_opcode_decodes_strictly() {
+        let illegal = (0x00..=0xFF)
+            .filter(|&opcode| {
+                Instruction::decode_strict(&mut Cursor::new(vec![opcode, 0x00, 0x00])).is_err()
+            })
+            .count();
+
+        assert_eq!(illegal, 11);
+    }
+
+    #[test]
+    fn test_operands_are_little_endian() {
+        let decode = |bytes: Vec<u8>| Instruction::decode(&mut Cursor::new(bytes)).unwrap();
+
+        assert!(matches!(
+            decode(vec![0xC3, 0x50, 0x01]),
+            Instruction::AbsoluteJump { address: 0x0150 }
+        ));
+        assert!(matches!(
+            decode(vec![0x01, 0x34, 0x12]),
+            Instruction::LoadTwoBytesOfDataIntoRegister {
+                data: 0x1234,
+                register: Register::BC,
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCD, 0x00, 0x40]),
+            Instruction::Call { address: 0x4000 }
+        ));
+        assert!(matches!(
+            decode(vec![0xFA, 0x00, 0xC0]),
+            Instruction::LoadAccumulatorFromMemory { address: 0xC000 }
+        ));
+    }
+
+    #[test]
+    fn test_fixed_opcodes() {
+        let decode = |bytes: Vec<u8>| {
+            let mut memory = Cursor::new(bytes);
+            let instruction = Instruction::decode(&mut memory).unwrap();
+
+            (instruction, memory.position())
+        };
+
+        assert!(matches!(
+            decode(vec![0x12]).0,
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register1: Register::A,
+                register2: Register::DE,
+                treat_value_in_second_register_as_memory_address: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0x22]).0,
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register1: Register::A,
+                register2: Register::HL,
+                operation_on_second_register: Some(MathOperation::Increment),
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0x32]).0,
+            Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                register2: Register::HL,
+                operation_on_second_register: Some(MathOperation::Decrement),
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCB, 0x00]).0,
+            Instruction::RotateContentOfRegisterToLeft {
+                register: Register::B,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCB, 0x08]).0,
+            Instruction::RotateContentOfRegisterToRight {
+                register: Register::B,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCB, 0x10]).0,
+            Instruction::RotateContentOfRegisterToLeftThroughCarryFlag {
+                register: Register::B,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCB, 0x1F]).0,
+            Instruction::RotateContentOfRegisterToRightThroughCarryFlag {
+                register: Register::A,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xCB, 0x8E]).0,
+            Instruction::ResetNthBitOfRegister {
+                nth: 1,
+                register: Register::HL,
+                treat_value_in_register_as_memory_address: true,
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0x80]).0,
+            Instruction::AddValueOfSecondRegisterToFirstRegister {
+                register1: Register::A,
+                register2: Register::B,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0x91]).0,
+            Instruction::SubtractValueOfSecondRegisterFromFirstRegister {
+                register1: Register::A,
+                register2: Register::C,
+                ..
+            }
+        ));
+        assert!(matches!(
+            decode(vec![0xE8, 0xFE]),
+            (Instruction::AddValueToStackPointer { value: 0xFE }, 2)
+        ));
+        assert!(matches!(
+            decode(vec![0xF8, 0x05]),
+            (
+                Instruction::AddValueToStackPointerAndStoreResultInRegisterHL { value: 0x05 },
+                2
+            )
+        ));
+    }
+
+    #[test]
+    fn test_halt_is_not_a_load() {
+        assert_eq!(
+            Instruction::decode(&mut Cursor::new(vec![0x76])).unwrap(),
+            Instruction::Halt
+        );
+    }
+
+    #[test]
+    fn test_loads_into_memory_pointed_by_hl() {
+        for opcode in (0x70..=0x75).chain(std::iter::once(0x77)) {
+            assert!(matches!(
+                Instruction::decode(&mut Cursor::new(vec![opcode])).unwrap(),
+                Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                    register2: Register::HL,
+                    treat_value_in_first_register_as_memory_address: false,
+                    treat_value_in_second_register_as_memory_address: true,
+                    ..
+                }
+            ));
+        }
+
+        for opcode in 0x78..=0x7F {
+            assert!(matches!(
+                Instruction::decode(&mut Cursor::new(vec![opcode])).unwrap(),
+                Instruction::LoadValueOfFirstRegisterIntoSecondRegister {
+                    register2: Register::A,
+                    treat_value_in_second_register_as_memory_address: false,
+                    ..
+                }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let decode = |bytes: Vec<u8>| Instruction::decode(&mut Cursor::new(bytes)).unwrap();
+
+        for (opcode, condition) in [
+            (0x00, Condition::NZ),
+            (0x08, Condition::Z),
+            (0x10, Condition::NC),
+            (0x18, Condition::C),
+        ] {
+            assert_eq!(
+                decode(vec![0xC2 | opcode, 0x00, 0x00]),
+                Instruction::ConditionalAbsoluteJump {
+                    condition,
+                    address: 0x0000,
+                }
+            );
+            assert_eq!(
+                decode(vec![0x20 | opcode, 0x00]),
+                Instruction::ConditionalRelativeJump {
+                    condition,
+                    steps: 0x00,
+                }
+            );
+            assert_eq!(
+                decode(vec![0xC0 | opcode]),
+                Instruction::ConditionalReturn { condition }
+            );
+            assert_eq!(
+                decode(vec![0xC4 | opcode, 0x00, 0x00]),
+                Instruction::ConditionalCall {
+                    condition,
+                    address: 0x0000,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_branch_target() {
+        let target = |bytes: Vec<u8>| {
+            Instruction::decode(&mut Cursor::new(bytes))
+                .unwrap()
+                .branch_target(0x0150)
+        };
+
+        assert_eq!(target(vec![0xC3, 0x00, 0x40]), Some(0x4000));
+        assert_eq!(target(vec![0xDC, 0x34, 0x12]), Some(0x1234));
+        assert_eq!(target(vec![0x18, 0xFE]), Some(0x0150));
+        assert_eq!(target(vec![0x20, 0x05]), Some(0x0157));
+        assert_eq!(target(vec![0xEF]), Some(0x0028));
+        assert_eq!(target(vec![0xE9]), None);
+        assert_eq!(target(vec![0xC9]), None);
+    }
+
+    #[test]
+    fn test_decode_at() {
+        let decoded = Instruction::decode_at(&mut Cursor::new(vec![0x38, 0xF0]), 0x4123).unwrap();
+
+        assert_eq!(
+            decoded,
+            DecodedInstruction {
+                address: 0x4123,
+                instruction: Instruction::ConditionalRelativeJump {
+                    condition: Condition::C,
+                    steps: 0xF0,
+                },
+                length: 2,
+                branch_target: Some(0x4115),
+            }
+        );
+        assert_eq!(
+            Instruction::decode_at(&mut Cursor::new(vec![0x00]), 0x4123)
+                .unwrap()
+                .branch_target,
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_slice() {
+        let rom = [0xC3, 0x50, 0x01, 0x00];
+
+        assert_eq!(
+            Instruction::decode_slice(&rom).unwrap(),
+            (Instruction::AbsoluteJump { address: 0x0150 }, 3)
+        );
+        assert_eq!(
+            Instruction::decode_slice(&rom[3..]).unwrap(),
+            (Instruction::NoOperation, 1)
+        );
+        assert!(Instruction::decode_slice(&rom[..2]).is_err());
+    }
+
+    #[test]
+    fn test_stop_operand() {
+        let (stop, bytes) =
+            Instruction::decode_with_bytes(&mut Cursor::new(vec![0x10, 0x00])).unwrap();
+        let (corrupted_stop, corrupted_bytes) =
+            Instruction::decode_with_bytes(&mut Cursor::new(vec![0x10, 0xFF])).unwrap();
+
+        assert_eq!(stop, Instruction::Stop { operand: 0x00 });
+        assert_eq!(bytes, vec![0x10, 0x00]);
+        assert_eq!(stop.to_string(), "STOP");
+        assert_eq!(corrupted_stop, Instruction::Stop { operand: 0xFF });
+        assert_eq!(corrupted_bytes, vec![0x10, 0xFF]);
+        assert_eq!(corrupted_stop.to_string(), "STOP $FF");
+    }
+
+    #[test]
+    fn test_decode_with_bytes() {
+        let mut memory = Cursor::new(vec![0xC3, 0x50, 0x01, 0xCB, 0x7C]);
+
+        assert_eq!(
+            Instruction::decode_with_bytes(&mut memory).unwrap(),
+            (
+                Instruction::AbsoluteJump { address: 0x0150 },
+                vec![0xC3, 0x50, 0x01]
+            )
+        );
+        assert_eq!(
+            Instruction::decode_with_bytes(&mut memory).unwrap(),
+            (
+                Instruction::CopyNthBitOfRegisterToZFlag {
+                    nth: 7,
+                    register: Register::H,
+                    treat_value_in_register_as_memory_address: false,
+                },
+                vec![0xCB, 0x7C]
+            )
+        );
+    }
+
+    #[test]
+    fn test_kinds_match_variants() {
+        let instructions = (0x00..=0xFF)
+            .flat_map(|opcode| [vec![opcode, 0x00, 0x00], vec![0xCB, opcode]])
+            .filter_map(|bytes| Instruction::decode(&mut Cursor::new(bytes)).ok())
+            .collect::<Vec<_>>();
+
+        for a in &instructions {
+            for b in &instructions {
+                assert_eq!(
+                    std::mem::discriminant(a) == std::mem::discriminant(b),
+                    a.kind() == b.kind(),
+                    "{:?} and {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let disassemble = |bytes: Vec<u8>| {
+            Instruction::decode(&mut Cursor::new(bytes))
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(disassemble(vec![0x00]), "NOP");
+        assert_eq!(disassemble(vec![0x22]), "LD (HL+),A");
+        assert_eq!(disassemble(vec![0x3A]), "LD A,(HL-)");
+        assert_eq!(disassemble(vec![0x41]), "LD B,C");
+        assert_eq!(disassemble(vec![0x86]), "ADD A,(HL)");
+        assert_eq!(disassemble(vec![0x18, 0xFE]), "JR -2");
+        assert_eq!(disassemble(vec![0xCF]), "RST $08");
+        assert_eq!(disassemble(vec![0xE8, 0x05]), "ADD SP,+5");
+        assert_eq!(disassemble(vec![0xCB, 0x0E]), "RRC (HL)");
+        assert_eq!(disassemble(vec![0xCB, 0x7F]), "BIT 7,A");
+        assert_eq!(disassemble(vec![0x8E]), "ADC A,(HL)");
+        assert_eq!(disassemble(vec![0x9A]), "SBC A,D");
+        assert_eq!(disassemble(vec![0xD3]), "DB $D3");
+    }
+
+    #[test]
+    fn test_basic_block_ends_at_jump() {
+        let bytes = vec![0x00, 0x00, 0x3C, 0x06, 0x10, 0xC3, 0x00, 0x01, 0x3C];
+
+        let (instructions, end) = decode_basic_block(&bytes, 1);
+
+        assert_eq!(instructions.len(), 4);
+        assert!(matches!(instructions[3], Instruction::AbsoluteJump { .. }));
+        assert_eq!(end, 8);
+    }
+
+    #[test]
+    fn test_basic_block_ends_at_invalid_opcode() {
+        let bytes = vec![0x00, 0x3C, 0xD3, 0x00];
+
+        let (instructions, end) = decode_basic_block(&bytes, 0);
+
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[2], Instruction::IllegalOpcode { opcode: 0xD3 });
+        assert_eq!(end, 3);
+    }
+
+    #[test]
+    fn test_basic_block_stops_at_end_of_address_space() {
+        let bytes = vec![0x00; 0x10010];
+
+        let (instructions, end) = decode_basic_block(&bytes, 0xFFF0);
+
+        assert_eq!(instructions.len(), 0x0F);
+        assert_eq!(end, 0xFFFF);
     }
 }
@@ -0,0 +1,68 @@
+/// Bits of the I/O register at `address` that are hard-wired to read back as 1, whatever was
+/// last written to them. Unmapped I/O addresses and write-only registers read back as 0xFF, and
+/// DMA reads back the last value written to it.
+pub fn read_back_mask(address: u16) -> u8 {
+    match address {
+        // P1, SC and TAC
+        0xFF00 => 0b11000000,
+        0xFF02 => 0b01111110,
+        0xFF07 => 0b11111000,
+        // IF
+        0xFF0F => 0b11100000,
+        // NR10, NR30, NR32 and NR52
+        0xFF10 => 0b10000000,
+        0xFF1A => 0b01111111,
+        0xFF1C => 0b10011111,
+        0xFF26 => 0b01110000,
+        // Write-only bits of the sound registers: NRx1 lengths, NRx3 frequencies and NRx4
+        // triggers and frequencies.
+        0xFF11 | 0xFF16 => 0b00111111,
+        0xFF13 | 0xFF18 | 0xFF1B | 0xFF1D | 0xFF20 => 0b11111111,
+        0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => 0b10111111,
+        // STAT
+        0xFF41 => 0b10000000,
+        // Unmapped
+        0xFF03 | 0xFF08..=0xFF0E | 0xFF15 | 0xFF1F | 0xFF27..=0xFF2F | 0xFF4C..=0xFF7F => {
+            0b11111111
+        }
+        _ => 0b00000000,
+    }
+}
+
+/// What the CPU reads from the I/O register at `address` when `value` was last written to it.
+pub fn read_back(address: u16, value: u8) -> u8 {
+    value | read_back_mask(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_back_masks() {
+        assert_eq!(read_back(0xFF41, 0x00), 0x80);
+        assert_eq!(read_back(0xFF26, 0x00), 0x70);
+        assert_eq!(read_back(0xFF26, 0x8F), 0xFF);
+        assert_eq!(read_back(0xFF00, 0x00), 0xC0);
+        assert_eq!(read_back(0xFF00, 0x2F), 0xEF);
+        assert_eq!(read_back(0xFF40, 0x00), 0x00);
+    }
+
+    #[test]
+    fn test_write_only_registers() {
+        assert_eq!(read_back(0xFF13, 0x12), 0xFF);
+        assert_eq!(read_back(0xFF14, 0x40), 0xFF);
+        assert_eq!(read_back(0xFF14, 0x00), 0xBF);
+        assert_eq!(read_back(0xFF11, 0x80), 0xBF);
+        assert_eq!(read_back(0xFF46, 0xC0), 0xC0);
+    }
+
+    #[test]
+    fn test_unmapped_registers_read_back_as_0xff() {
+        assert_eq!(read_back(0xFF03, 0x00), 0xFF);
+        assert_eq!(read_back(0xFF08, 0x00), 0xFF);
+        assert_eq!(read_back(0xFF27, 0x12), 0xFF);
+        assert_eq!(read_back(0xFF7F, 0x00), 0xFF);
+        assert_eq!(read_back(0xFF80, 0x00), 0x00);
+    }
+}
@@ -0,0 +1,115 @@
+const SELECT_DIRECTIONS: u8 = 0b00010000;
+const SELECT_ACTIONS: u8 = 0b00100000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    /// Whether the button belongs to the direction group rather than the action one.
+    pub fn is_direction(self) -> bool {
+        matches!(
+            self,
+            Button::Right | Button::Left | Button::Up | Button::Down
+        )
+    }
+
+    /// The button's bit in the low nibble of P1 while its group is selected.
+    pub fn mask(self) -> u8 {
+        match self {
+            Button::Right | Button::A => 0b0001,
+            Button::Left | Button::B => 0b0010,
+            Button::Up | Button::Select => 0b0100,
+            Button::Down | Button::Start => 0b1000,
+        }
+    }
+}
+
+/// The pressed buttons, as seen through P1 (0xFF00).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Joypad {
+    directions: u8,
+    actions: u8,
+}
+
+impl Joypad {
+    pub fn press(&mut self, button: Button) {
+        *self.group(button) |= button.mask();
+    }
+
+    pub fn release(&mut self, button: Button) {
+        *self.group(button) &= !button.mask();
+    }
+
+    /// What P1 reads as when `select` was last written to it.
+    ///
+    /// Bits 4 and 5 select the direction and action groups when low. Pressed buttons of the
+    /// selected groups pull their bit of the low nibble low, so with both groups selected the
+    /// nibble is the AND of the two, and with neither it reads as 0xF.
+    pub fn read(&self, select: u8) -> u8 {
+        let mut pressed = 0;
+
+        if select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.directions;
+        }
+
+        if select & SELECT_ACTIONS == 0 {
+            pressed |= self.actions;
+        }
+
+        0b11000000 | (select & (SELECT_DIRECTIONS | SELECT_ACTIONS)) | (!pressed & 0b1111)
+    }
+
+    fn group(&mut self, button: Button) -> &mut u8 {
+        if button.is_direction() {
+            &mut self.directions
+        } else {
+            &mut self.actions
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_both_groups_selected() {
+        let mut joypad = Joypad::default();
+
+        joypad.press(Button::Down);
+        joypad.press(Button::A);
+
+        assert_eq!(joypad.read(0x00) & 0b1111, 0b0110);
+        assert_eq!(joypad.read(SELECT_ACTIONS) & 0b1111, 0b0111);
+        assert_eq!(joypad.read(SELECT_DIRECTIONS) & 0b1111, 0b1110);
+    }
+
+    #[test]
+    fn test_neither_group_selected() {
+        let mut joypad = Joypad::default();
+
+        joypad.press(Button::Start);
+        joypad.press(Button::Left);
+
+        assert_eq!(joypad.read(0x30), 0xFF);
+    }
+
+    #[test]
+    fn test_release() {
+        let mut joypad = Joypad::default();
+
+        joypad.press(Button::B);
+        joypad.release(Button::B);
+
+        assert_eq!(joypad.read(0x10), 0xDF);
+    }
+}
@@ -1,7 +1,1304 @@
+mod bus_stats;
+mod cheat;
+mod condition;
+mod dirty_vram;
+mod disassembler;
+mod execute;
 mod flag;
+mod idiom;
 mod instruction;
+mod interrupt;
+mod io;
+mod joypad;
+mod mnemonic;
+mod profiler;
 mod register;
+mod snapshot;
+mod stop;
+mod watchpoint;
 
+pub use bus_stats::*;
+pub use cheat::*;
+pub use condition::*;
+pub use dirty_vram::*;
+pub use disassembler::*;
 pub use flag::*;
+pub use idiom::*;
 pub use instruction::*;
+pub use interrupt::*;
+pub use io::*;
+pub use joypad::*;
+pub use mnemonic::*;
+pub use profiler::*;
 pub use register::*;
+pub use snapshot::*;
+pub use stop::*;
+pub use watchpoint::*;
+
+use crate::error::Result;
+use std::io::Cursor;
+use std::time::Duration;
+
+const MEMORY_SIZE: usize = 0x10000;
+const ROM_SIZE: usize = 0x8000;
+const EXTERNAL_RAM_START: u16 = 0xA000;
+const EXTERNAL_RAM_END: u16 = 0xBFFF;
+const IO_REGISTERS_START: u16 = 0xFF00;
+const IO_REGISTERS_END: u16 = 0xFF7F;
+
+/// How often front-ends should persist external RAM while `Cpu::ram_is_dirty` is set.
+pub const SAVE_RAM_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// An interrupt was dispatched or an instruction executed, taking this many cycles.
+    Executed(u8),
+    /// PC left the loaded program while the out-of-bounds trap was enabled. Nothing was executed.
+    OutOfBounds(u16),
+    /// In strict mode, the instruction, taking `cycles`, read or wrote an I/O register this build
+    /// doesn't emulate. The access still went through to plain memory.
+    UnimplementedAccess { addr: u16, write: bool, cycles: u8 },
+    /// The instruction executed, taking `cycles`, and wrote `value` to a watched address.
+    Watchpoint { address: u16, value: u8, cycles: u8 },
+}
+
+pub struct Cpu {
+    registers: Registers,
+    memory: Vec<u8>,
+    ime: bool,
+    ime_scheduled: bool,
+    halted: bool,
+    profiler: Option<Profiler>,
+    cheats: Vec<GameGenieCode>,
+    bus_stats: Option<BusStats>,
+    ram_dirty: bool,
+    dirty_vram: Option<DirtyVram>,
+    strict: bool,
+    unimplemented_access: Option<(u16, bool)>,
+    watchpoints: Vec<Watchpoint>,
+    triggered_watchpoint: Option<(u16, u8)>,
+    queued_watchpoint: Option<(u16, u8)>,
+    program_length: usize,
+    trap_out_of_bounds: bool,
+}
+
+impl Cpu {
+    /// Creates a CPU with `program` loaded at 0x0000 into an otherwise zeroed 64KB address space.
+    pub fn new(program: &[u8]) -> Cpu {
+        let mut memory = vec![0; MEMORY_SIZE];
+        let length = program.len().min(MEMORY_SIZE);
+
+        memory[..length].copy_from_slice(&program[..length]);
+
+        Cpu {
+            registers: Registers::default(),
+            memory,
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            profiler: None,
+            cheats: Vec::new(),
+            bus_stats: None,
+            ram_dirty: false,
+            dirty_vram: None,
+            strict: false,
+            unimplemented_access: None,
+            watchpoints: Vec::new(),
+            triggered_watchpoint: None,
+            queued_watchpoint: None,
+            program_length: length,
+            trap_out_of_bounds: false,
+        }
+    }
+
+    /// Creates a CPU with the first 32KB of `rom` mapped at 0x0000, in the state the DMG boot ROM
+    /// leaves it in when it jumps to the cartridge's entry point. The rest of the address space is
+    /// zeroed.
+    pub fn new_post_boot(rom: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(&rom[..rom.len().min(ROM_SIZE)]);
+        // The boot ROM ends with H and C reflecting whether the header checksum at 0x014D is
+        // non-zero.
+        let header_checksum_is_non_zero = cpu.peek(0x014D) != 0x00;
+        let flags = Flags {
+            z: true,
+            n: false,
+            h: header_checksum_is_non_zero,
+            cy: header_checksum_is_non_zero,
+        };
+
+        cpu.registers.write(Register::A, 0x01);
+        cpu.registers.set_flags(flags);
+        cpu.registers.write(Register::BC, 0x0013);
+        cpu.registers.write(Register::DE, 0x00D8);
+        cpu.registers.write(Register::HL, 0x014D);
+        cpu.registers.write(Register::SP, 0xFFFE);
+        cpu.registers.write(Register::PC, 0x0100);
+        cpu.set_interrupt_flags(Interrupt::VBlank.mask());
+        cpu.set_interrupt_enable(0x00);
+
+        cpu
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.registers.write(Register::PC, value);
+    }
+
+    pub fn set_sp(&mut self, value: u16) {
+        self.registers.write(Register::SP, value);
+    }
+
+    /// Reads IF. Its upper three bits are unused and always read as set.
+    pub fn interrupt_flags(&self) -> u8 {
+        self.peek(INTERRUPT_FLAG_ADDRESS) | 0b11100000
+    }
+
+    /// Writes IF, ignoring its unused upper three bits.
+    pub fn set_interrupt_flags(&mut self, value: u8) {
+        self.memory[INTERRUPT_FLAG_ADDRESS as usize] = value & 0b00011111;
+    }
+
+    pub fn interrupt_enable(&self) -> u8 {
+        self.peek(INTERRUPT_ENABLE_ADDRESS)
+    }
+
+    pub fn set_interrupt_enable(&mut self, value: u8) {
+        self.memory[INTERRUPT_ENABLE_ADDRESS as usize] = value;
+    }
+
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    /// Sets IME immediately, cancelling a pending EI.
+    pub fn set_ime(&mut self, value: bool) {
+        self.ime = value;
+        self.ime_scheduled = false;
+    }
+
+    /// Raises `interrupt` in IF, as the peripheral owning it would.
+    pub fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.memory[INTERRUPT_FLAG_ADDRESS as usize] |= interrupt.mask();
+    }
+
+    /// Reads a byte without any side effects.
+    pub fn peek(&self, address: u16) -> u8 {
+        let value = self.memory[address as usize];
+
+        self.cheats
+            .iter()
+            .fold(value, |value, cheat| cheat.patch(address, value))
+    }
+
+    /// Captures the whole address space through `peek`, so taking a snapshot has no side effects.
+    pub fn memory_snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot::new((0..=u16::MAX).map(|address| self.peek(address)).collect())
+    }
+
+    /// One-line summary of the register file and IME, e.g.
+    /// `AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100 IME=0`.
+    pub fn state_string(&self) -> String {
+        format!(
+            "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X} IME={}",
+            self.registers.read(Register::AF),
+            self.registers.read(Register::BC),
+            self.registers.read(Register::DE),
+            self.registers.read(Register::HL),
+            self.registers.read(Register::SP),
+            self.registers.read(Register::PC),
+            self.ime as u8
+        )
+    }
+
+    /// Decodes the instruction at PC along with its disassembly, without executing it.
+    pub fn current_instruction(&self) -> Result<(Instruction, String)> {
+        let (instruction, _) = self.decode_at_pc()?;
+        let disassembly = instruction.to_string();
+
+        Ok((instruction, disassembly))
+    }
+
+    /// Decodes up to `count` instructions starting at PC without executing them, stopping early at
+    /// an illegal opcode.
+    pub fn disassemble_ahead(&self, count: usize) -> Vec<(u16, Instruction)> {
+        let mut address = self.registers.read(Register::PC);
+        let mut instructions = Vec::with_capacity(count);
+
+        while instructions.len() < count {
+            match self.decode_at(address) {
+                Ok((instruction, length)) => {
+                    instructions.push((address, instruction));
+                    address = address.wrapping_add(length);
+                }
+                Err(_) => break,
+            }
+        }
+
+        instructions
+    }
+
+    /// Either dispatches a pending interrupt or fetches and executes a single instruction.
+    pub fn step(&mut self) -> Result<StepResult> {
+        let pc = self.registers.read(Register::PC);
+
+        // A watchpoint hit by the same instruction as a strict-mode trap is reported on its own,
+        // before anything else executes.
+        if let Some((address, value)) = self.queued_watchpoint.take() {
+            return Ok(StepResult::Watchpoint {
+                address,
+                value,
+                cycles: 0,
+            });
+        }
+
+        // Writes made outside of `step`, e.g. through `execute`, aren't reported by it.
+        self.triggered_watchpoint = None;
+        self.unimplemented_access = None;
+
+        // A pending interrupt ends HALT regardless of IME. Without IME, execution simply continues
+        // after the HALT.
+        let woke_up = self.halted && self.pending_interrupt().is_some();
+
+        if woke_up {
+            self.halted = false;
+        }
+
+        let cycles = match self.pending_interrupt() {
+            // Leaving HALT costs an extra M-cycle before the dispatch begins.
+            Some(interrupt) if self.ime => self.dispatch(interrupt) + if woke_up { 4 } else { 0 },
+            _ if self.halted => 4,
+            _ if self.trap_out_of_bounds && pc as usize >= self.program_length => {
+                return Ok(StepResult::OutOfBounds(pc));
+            }
+            _ => {
+                let decoded = self.decode_at_pc();
+
+                #[cfg(feature = "logging")]
+                if let Err(crate::error::OniError::Decode(
+                    crate::error::DecodeError::IllegalOpcode(opcode),
+                )) = &decoded
+                {
+                    log::debug!(
+                        target: "oni::cpu",
+                        "illegal opcode {:#04X} at {:#06X}",
+                        opcode,
+                        pc
+                    );
+                }
+
+                let (instruction, length) = decoded?;
+
+                if let Some(bus_stats) = &mut self.bus_stats {
+                    for offset in 0..length {
+                        bus_stats.record_read(pc.wrapping_add(offset));
+                    }
+                }
+
+                // EI only takes effect after the instruction following it, unless that
+                // instruction is a DI cancelling it.
+                let enable_ime = self.ime_scheduled;
+
+                self.registers.write(Register::PC, pc.wrapping_add(length));
+
+                let cycles = match self.execute(instruction) {
+                    Ok(cycles) => cycles,
+                    Err(error) => {
+                        self.triggered_watchpoint = None;
+                        self.unimplemented_access = None;
+
+                        return Err(error);
+                    }
+                };
+
+                if enable_ime && self.ime_scheduled {
+                    self.ime = true;
+                    self.ime_scheduled = false;
+                }
+
+                cycles
+            }
+        };
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, cycles);
+        }
+
+        match (
+            self.unimplemented_access.take(),
+            self.triggered_watchpoint.take(),
+        ) {
+            (Some((addr, write)), watchpoint) => {
+                self.queued_watchpoint = watchpoint;
+
+                Ok(StepResult::UnimplementedAccess {
+                    addr,
+                    write,
+                    cycles,
+                })
+            }
+            (None, Some((address, value))) => Ok(StepResult::Watchpoint {
+                address,
+                value,
+                cycles,
+            }),
+            (None, None) => Ok(StepResult::Executed(cycles)),
+        }
+    }
+
+    /// Makes `step` stop with `StepResult::OutOfBounds` instead of fetching past the end of the
+    /// program the CPU was created with, so runaway test programs fail fast rather than executing
+    /// zeroed memory as NOPs.
+    pub fn set_out_of_bounds_trap(&mut self, enabled: bool) {
+        self.trap_out_of_bounds = enabled;
+    }
+
+    /// Applies a Game Genie code to every following ROM read, including instruction fetches.
+    pub fn add_cheat(&mut self, code: &str) -> Result<()> {
+        self.cheats.push(GameGenieCode::parse(code)?);
+
+        Ok(())
+    }
+
+    /// Removes a code added with `add_cheat`, returning whether it was applied.
+    pub fn remove_cheat(&mut self, code: &str) -> bool {
+        let Ok(code) = GameGenieCode::parse(code) else {
+            return false;
+        };
+        let length = self.cheats.len();
+
+        self.cheats.retain(|cheat| *cheat != code);
+
+        self.cheats.len() != length
+    }
+
+    /// Makes `step` return `StepResult::UnimplementedAccess` when an instruction touches an I/O
+    /// register other than IF. Only IF is emulated, every other register is plain memory that
+    /// would otherwise hide what a ROM expected from it.
+    pub fn set_strict_mode(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// Keeps stepping until `condition` holds.
+    pub fn run_until(&mut self, mut condition: StopCondition) -> Result<StopReason> {
+        let mut cycles = 0;
+        let mut instructions = 0;
+
+        loop {
+            match self.step()? {
+                StepResult::Executed(step_cycles) => cycles += step_cycles as u64,
+                StepResult::OutOfBounds(pc) => return Ok(StopReason::OutOfBounds(pc)),
+                StepResult::UnimplementedAccess {
+                    addr,
+                    write,
+                    cycles: step_cycles,
+                } => {
+                    cycles += step_cycles as u64;
+
+                    return Ok(StopReason::UnimplementedAccess {
+                        addr,
+                        write,
+                        cycles,
+                    });
+                }
+                StepResult::Watchpoint { address, value, .. } => {
+                    return Ok(StopReason::Watchpoint { address, value })
+                }
+            }
+
+            instructions += 1;
+
+            let reason = match &mut condition {
+                StopCondition::Cycles(target) => {
+                    (cycles >= *target).then_some(StopReason::CyclesElapsed(cycles))
+                }
+                StopCondition::Instructions(target) => (instructions >= *target)
+                    .then_some(StopReason::InstructionsExecuted(instructions)),
+                StopCondition::Pc(address) => (self.registers.read(Register::PC) == *address)
+                    .then_some(StopReason::PcReached(*address)),
+                StopCondition::Custom(is_met) => is_met(self).then_some(StopReason::ConditionMet),
+            };
+
+            if let Some(reason) = reason {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Starts attributing executed cycles to the regions they were spent in, discarding any
+    /// previously collected profile.
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Stops profiling and returns the collected profile.
+    pub fn disable_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Starts counting memory accesses per region, discarding any previously collected counts.
+    pub fn enable_bus_stats(&mut self) {
+        self.bus_stats = Some(BusStats::default());
+    }
+
+    /// Stops counting memory accesses and returns the collected counts.
+    pub fn disable_bus_stats(&mut self) -> Option<BusStats> {
+        self.bus_stats.take()
+    }
+
+    pub fn bus_stats(&self) -> Option<&BusStats> {
+        self.bus_stats.as_ref()
+    }
+
+    /// Whether external RAM was written to since the last `save_ram`.
+    pub fn ram_is_dirty(&self) -> bool {
+        self.ram_dirty
+    }
+
+    /// Returns external RAM (0xA000-0xBFFF) for persisting and clears the dirty flag.
+    pub fn save_ram(&mut self) -> &[u8] {
+        self.ram_dirty = false;
+
+        &self.memory[EXTERNAL_RAM_START as usize..=EXTERNAL_RAM_END as usize]
+    }
+
+    /// Starts tracking which tiles and tile map entries get written to.
+    pub fn enable_vram_tracking(&mut self) {
+        self.dirty_vram = Some(DirtyVram::default());
+    }
+
+    pub fn disable_vram_tracking(&mut self) {
+        self.dirty_vram = None;
+    }
+
+    /// Returns what was written to VRAM since the last call, or nothing if tracking is disabled.
+    pub fn take_dirty_vram(&mut self) -> DirtyVram {
+        self.dirty_vram
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes every watchpoint on `address`.
+    pub fn remove_watchpoints(&mut self, address: u16) {
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.address != address);
+    }
+
+    /// Highest priority interrupt that's both requested in IF and enabled in IE.
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        let requested = self.peek(INTERRUPT_FLAG_ADDRESS) & self.peek(INTERRUPT_ENABLE_ADDRESS);
+
+        Interrupt::highest_priority(requested)
+    }
+
+    /// Acknowledges `interrupt` and jumps to its handler.
+    ///
+    /// Only the serviced interrupt's IF bit is cleared, lower priority ones stay requested.
+    fn dispatch(&mut self, interrupt: Interrupt) -> u8 {
+        let pc = self.registers.read(Register::PC);
+
+        #[cfg(feature = "logging")]
+        log::debug!(
+            target: "oni::cpu",
+            "dispatching {:?} interrupt from {:#06X}",
+            interrupt,
+            pc
+        );
+
+        self.memory[INTERRUPT_FLAG_ADDRESS as usize] &= !interrupt.mask();
+        self.ime = false;
+        self.push(pc);
+        self.registers.write(Register::PC, interrupt.vector());
+
+        20
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        if let Some(bus_stats) = &mut self.bus_stats {
+            bus_stats.record_read(address);
+        }
+
+        self.check_implemented(address, false);
+
+        read_back(address, self.peek(address))
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if let Some(bus_stats) = &mut self.bus_stats {
+            bus_stats.record_write(address);
+        }
+
+        if (EXTERNAL_RAM_START..=EXTERNAL_RAM_END).contains(&address) {
+            self.ram_dirty = true;
+        }
+
+        if let Some(dirty_vram) = &mut self.dirty_vram {
+            dirty_vram.record_write(address);
+        }
+
+        self.check_implemented(address, true);
+
+        if self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.is_triggered_by(address, value))
+        {
+            self.triggered_watchpoint = Some((address, value));
+        }
+
+        self.memory[address as usize] = value;
+    }
+
+    fn check_implemented(&mut self, address: u16, write: bool) {
+        if !self.strict
+            || address == INTERRUPT_FLAG_ADDRESS
+            || !(IO_REGISTERS_START..=IO_REGISTERS_END).contains(&address)
+        {
+            return;
+        }
+
+        #[cfg(feature = "logging")]
+        log::warn!(
+            target: "oni::cpu",
+            "unimplemented I/O register {} at {:#06X}",
+            if write { "write" } else { "read" },
+            address
+        );
+
+        self.unimplemented_access.get_or_insert((address, write));
+    }
+
+    fn push(&mut self, value: u16) {
+        let sp = self.registers.read(Register::SP).wrapping_sub(2);
+        let [high, low] = value.to_be_bytes();
+
+        self.registers.write(Register::SP, sp);
+        self.write(sp.wrapping_add(1), high);
+        self.write(sp, low);
+    }
+
+    fn decode_at_pc(&self) -> Result<(Instruction, u16)> {
+        self.decode_at(self.registers.read(Register::PC))
+    }
+
+    fn decode_at(&self, address: u16) -> Result<(Instruction, u16)> {
+        let bytes = (0..3)
+            .map(|offset| self.peek(address.wrapping_add(offset)))
+            .collect::<Vec<_>>();
+        let mut memory = Cursor::new(bytes);
+        let instruction = Instruction::decode_strict(&mut memory)?;
+
+        Ok((instruction, memory.position() as u16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_ahead() {
+        let mut cpu = Cpu::new(&[0x00, 0x00, 0x3C, 0xC3, 0x50, 0x01, 0x06, 0x10, 0xD3]);
+
+        cpu.set_pc(0x0002);
+        cpu.enable_bus_stats();
+
+        assert_eq!(
+            cpu.disassemble_ahead(5),
+            vec![
+                (
+                    0x0002,
+                    Instruction::IncrementValueInRegister {
+                        register: Register::A,
+                        treat_value_in_register_as_memory_address: false,
+                    }
+                ),
+                (0x0003, Instruction::AbsoluteJump { address: 0x0150 }),
+                (
+                    0x0006,
+                    Instruction::LoadOneByteOfDataIntoRegister {
+                        data: 0x10,
+                        register: Register::B,
+                        treat_value_in_register_as_memory_address: false,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(cpu.disassemble_ahead(1).len(), 1);
+        assert_eq!(cpu.bus_stats().unwrap().reads(MemoryRegion::Rom), 0);
+    }
+
+    #[test]
+    fn test_register_access() {
+        let mut cpu = Cpu::new(&[0x00, 0x00, 0x00, 0x37]);
+
+        cpu.set_pc(0x0003);
+        cpu.set_sp(0xDFF0);
+        cpu.registers_mut().write(Register::AF, 0x12FF);
+
+        assert_eq!(cpu.registers().read(Register::AF), 0x12F0);
+        assert_eq!(cpu.registers().read(Register::SP), 0xDFF0);
+
+        cpu.registers_mut().write(Register::AF, 0x1200);
+        cpu.step().unwrap();
+
+        assert!(cpu.registers().flags().cy);
+        assert_eq!(cpu.registers().read(Register::PC), 0x0004);
+    }
+
+    #[test]
+    fn test_interrupt_register_access() {
+        let mut cpu = Cpu::new(&[0x00]);
+
+        cpu.set_interrupt_flags(0xFF);
+
+        assert_eq!(cpu.interrupt_flags(), 0xFF);
+        assert_eq!(cpu.peek(INTERRUPT_FLAG_ADDRESS), 0b00011111);
+
+        cpu.set_interrupt_flags(Interrupt::Timer.mask());
+        cpu.set_interrupt_enable(Interrupt::Timer.mask());
+        cpu.set_ime(true);
+
+        assert_eq!(cpu.interrupt_flags(), 0b11100000 | Interrupt::Timer.mask());
+        assert_eq!(cpu.interrupt_enable(), Interrupt::Timer.mask());
+        assert!(cpu.ime());
+
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.registers.read(Register::PC), 0x0050);
+        assert!(!cpu.ime());
+    }
+
+    #[test]
+    fn test_request_interrupt() {
+        let mut cpu = Cpu::new(&[0x00, 0x00]);
+
+        cpu.write(INTERRUPT_ENABLE_ADDRESS, Interrupt::VBlank.mask());
+        cpu.write(INTERRUPT_FLAG_ADDRESS, Interrupt::Serial.mask());
+        cpu.ime = true;
+        cpu.request_interrupt(Interrupt::VBlank);
+
+        assert_eq!(
+            cpu.peek(INTERRUPT_FLAG_ADDRESS),
+            Interrupt::VBlank.mask() | Interrupt::Serial.mask()
+        );
+
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.registers.read(Register::PC), 0x0040);
+    }
+
+    #[test]
+    fn test_interrupt_priority() {
+        let mut cpu = Cpu::new(&[]);
+
+        cpu.registers.write(Register::PC, 0x0150);
+        cpu.registers.write(Register::SP, 0xFFFE);
+        cpu.write(INTERRUPT_ENABLE_ADDRESS, 0b11111);
+        cpu.write(
+            INTERRUPT_FLAG_ADDRESS,
+            Interrupt::VBlank.mask() | Interrupt::Timer.mask(),
+        );
+        cpu.ime = true;
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(20));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0040);
+        assert_eq!(cpu.registers.read(Register::SP), 0xFFFC);
+        assert_eq!(cpu.peek(0xFFFC), 0x50);
+        assert_eq!(cpu.peek(0xFFFD), 0x01);
+        assert_eq!(cpu.peek(INTERRUPT_FLAG_ADDRESS), Interrupt::Timer.mask());
+        assert!(!cpu.ime);
+
+        cpu.ime = true;
+
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.registers.read(Register::PC), 0x0050);
+        assert_eq!(cpu.peek(INTERRUPT_FLAG_ADDRESS), 0x00);
+    }
+
+    #[test]
+    fn test_dispatch_is_not_a_bus_write() {
+        let mut cpu = Cpu::new(&[0x00]);
+
+        cpu.registers.write(Register::SP, 0xFFFE);
+        cpu.set_interrupt_flags(Interrupt::Timer.mask());
+        cpu.set_interrupt_enable(Interrupt::Timer.mask());
+        cpu.set_ime(true);
+        cpu.enable_bus_stats();
+        cpu.add_watchpoint(Watchpoint::new(INTERRUPT_FLAG_ADDRESS));
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(20));
+        assert_eq!(cpu.peek(INTERRUPT_FLAG_ADDRESS), 0x00);
+        assert_eq!(cpu.bus_stats().unwrap().writes(MemoryRegion::Io), 0);
+    }
+
+    #[test]
+    fn test_interrupts_wait_for_ime() {
+        let mut cpu = Cpu::new(&[]);
+
+        cpu.write(INTERRUPT_ENABLE_ADDRESS, Interrupt::VBlank.mask());
+        cpu.write(INTERRUPT_FLAG_ADDRESS, Interrupt::VBlank.mask());
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0001);
+        assert_eq!(cpu.peek(INTERRUPT_FLAG_ADDRESS), Interrupt::VBlank.mask());
+    }
+
+    #[test]
+    fn test_enable_interrupts_then_disable() {
+        let mut cpu = Cpu::new(&[0xFB, 0xF3]);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert!(!cpu.ime);
+        assert!(!cpu.ime_scheduled);
+    }
+
+    #[test]
+    fn test_enable_interrupts_is_delayed_by_one_instruction() {
+        let mut cpu = Cpu::new(&[0xFB, 0x00, 0x00]);
+
+        cpu.registers.write(Register::SP, 0xFFFE);
+        cpu.write(INTERRUPT_ENABLE_ADDRESS, Interrupt::VBlank.mask());
+        cpu.write(INTERRUPT_FLAG_ADDRESS, Interrupt::VBlank.mask());
+
+        cpu.step().unwrap();
+
+        assert!(!cpu.ime);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0002);
+        assert!(cpu.ime);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(20));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0040);
+    }
+
+    #[test]
+    fn test_memory_snapshot_diff() {
+        let mut cpu = Cpu::new(&[0x00, 0x01, 0x02]);
+        let before = cpu.memory_snapshot();
+
+        cpu.write(0xC123, 0x05);
+
+        let after = cpu.memory_snapshot();
+
+        assert_eq!(before.diff(&after), vec![(0xC123, 0x00, 0x05)]);
+        assert_eq!(after.read(0x0002), 0x02);
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[cfg(feature = "logging")]
+    mod logging {
+        use super::*;
+        use log::{Level, Log, Metadata, Record};
+        use std::sync::Mutex;
+        use std::thread::{self, ThreadId};
+
+        struct CapturingLogger {
+            records: Mutex<Vec<(ThreadId, Level, String, String)>>,
+        }
+
+        impl Log for CapturingLogger {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &Record) {
+                self.records.lock().unwrap().push((
+                    thread::current().id(),
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ));
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: CapturingLogger = CapturingLogger {
+            records: Mutex::new(Vec::new()),
+        };
+
+        /// Records logged by the current thread, since tests running in parallel share the logger.
+        fn captured_records() -> Vec<(Level, String, String)> {
+            LOGGER
+                .records
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(thread, ..)| *thread == thread::current().id())
+                .map(|(_, level, target, message)| (*level, target.clone(), message.clone()))
+                .collect()
+        }
+
+        #[test]
+        fn test_events_are_logged() {
+            let _ = log::set_logger(&LOGGER);
+            log::set_max_level(log::LevelFilter::Trace);
+
+            let mut cpu = Cpu::new(&[0xD3]);
+
+            cpu.registers.write(Register::SP, 0xFFFE);
+            cpu.write(INTERRUPT_ENABLE_ADDRESS, Interrupt::Joypad.mask());
+            cpu.write(INTERRUPT_FLAG_ADDRESS, Interrupt::Joypad.mask());
+            cpu.ime = true;
+
+            cpu.step().unwrap();
+
+            cpu.registers.write(Register::PC, 0x0000);
+
+            assert!(cpu.step().is_err());
+            assert_eq!(
+                captured_records(),
+                vec![
+                    (
+                        Level::Debug,
+                        "oni::cpu".to_string(),
+                        "dispatching Joypad interrupt from 0x0000".to_string()
+                    ),
+                    (
+                        Level::Debug,
+                        "oni::cpu".to_string(),
+                        "illegal opcode 0xD3 at 0x0000".to_string()
+                    ),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_halt_wakes_on_interrupt() {
+        let mut cpu = Cpu::new(&[0xFB, 0x76, 0x00]);
+
+        cpu.memory[INTERRUPT_ENABLE_ADDRESS as usize] = Interrupt::Timer.mask();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+            assert_eq!(cpu.registers.read(Register::PC), 0x0002);
+        }
+
+        cpu.memory[INTERRUPT_FLAG_ADDRESS as usize] = Interrupt::Timer.mask();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(24));
+        assert_eq!(cpu.registers.read(Register::PC), Interrupt::Timer.vector());
+    }
+
+    #[test]
+    fn test_halt_wakes_without_ime() {
+        let mut cpu = Cpu::new(&[0x76, 0x37]);
+
+        cpu.memory[INTERRUPT_ENABLE_ADDRESS as usize] = Interrupt::Timer.mask();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0001);
+
+        cpu.memory[INTERRUPT_FLAG_ADDRESS as usize] = Interrupt::Timer.mask();
+        cpu.step().unwrap();
+
+        assert!(cpu.registers.flags().cy);
+        assert_eq!(cpu.registers.read(Register::PC), 0x0002);
+    }
+
+    #[test]
+    fn test_out_of_bounds_trap() {
+        let mut cpu = Cpu::new(&[0x00, 0x37]);
+
+        cpu.set_out_of_bounds_trap(true);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.step().unwrap(), StepResult::OutOfBounds(0x0002));
+        assert_eq!(cpu.registers.read(Register::PC), 0x0002);
+        assert_eq!(
+            cpu.run_until(StopCondition::Instructions(10)).unwrap(),
+            StopReason::OutOfBounds(0x0002)
+        );
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut program = vec![0x00; 0x0200];
+
+        program[0x0150..0x0153].copy_from_slice(&[0xC3, 0x00, 0x01]);
+
+        let mut cpu = Cpu::new(&program);
+
+        assert_eq!(
+            cpu.run_until(StopCondition::Cycles(10)).unwrap(),
+            StopReason::CyclesElapsed(12)
+        );
+        assert_eq!(
+            cpu.run_until(StopCondition::Instructions(5)).unwrap(),
+            StopReason::InstructionsExecuted(5)
+        );
+        assert_eq!(
+            cpu.run_until(StopCondition::Pc(0x0100)).unwrap(),
+            StopReason::PcReached(0x0100)
+        );
+        assert_eq!(
+            cpu.run_until(StopCondition::Custom(Box::new(|cpu| {
+                cpu.registers.read(Register::PC) == 0x0120
+            })))
+            .unwrap(),
+            StopReason::ConditionMet
+        );
+        assert_eq!(cpu.registers.read(Register::PC), 0x0120);
+    }
+
+    #[test]
+    fn test_bus_stats() {
+        let mut cpu = Cpu::new(&[0x77, 0x46, 0x22, 0x12, 0x1A]);
+
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.registers.write(Register::DE, 0x8000);
+        cpu.enable_bus_stats();
+
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+
+        let bus_stats = cpu.bus_stats().unwrap();
+
+        assert_eq!(bus_stats.reads(MemoryRegion::Rom), 5);
+        assert_eq!(bus_stats.reads(MemoryRegion::Wram), 1);
+        assert_eq!(bus_stats.writes(MemoryRegion::Wram), 2);
+        assert_eq!(bus_stats.reads(MemoryRegion::Vram), 1);
+        assert_eq!(bus_stats.writes(MemoryRegion::Vram), 1);
+        assert_eq!(bus_stats.writes(MemoryRegion::Hram), 0);
+    }
+
+    #[test]
+    fn test_io_reads_apply_read_back_masks() {
+        let mut cpu = Cpu::new(&[0xF2, 0xF2, 0xF2]);
+
+        cpu.write(INTERRUPT_FLAG_ADDRESS, Interrupt::VBlank.mask());
+
+        for (register, value) in [(0x08, 0xFF), (0x41, 0x80), (0x0F, 0xE1)] {
+            cpu.registers.write(Register::C, register);
+            cpu.step().unwrap();
+
+            assert_eq!(cpu.registers.read(Register::A), value);
+        }
+
+        assert_eq!(cpu.peek(0xFF08), 0x00);
+    }
+
+    #[test]
+    fn test_ram_dirty_flag() {
+        let mut cpu = Cpu::new(&[0x77, 0x77, 0x77]);
+
+        cpu.registers.write(Register::A, 0x42);
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.step().unwrap();
+
+        assert!(!cpu.ram_is_dirty());
+
+        cpu.registers.write(Register::HL, 0xA010);
+        cpu.step().unwrap();
+
+        assert!(cpu.ram_is_dirty());
+
+        let ram = cpu.save_ram();
+
+        assert_eq!(ram.len(), 0x2000);
+        assert_eq!(ram[0x10], 0x42);
+        assert!(!cpu.ram_is_dirty());
+
+        cpu.registers.write(Register::HL, 0xBFFF);
+        cpu.step().unwrap();
+
+        assert!(cpu.ram_is_dirty());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut cpu = Cpu::new(&[0x77, 0x3C, 0x77, 0x3C, 0x77, 0x00, 0x77]);
+
+        cpu.registers.write(Register::HL, 0xC123);
+        cpu.registers.write(Register::A, 0x04);
+        cpu.add_watchpoint(Watchpoint::with_condition(
+            0xC123,
+            ValueCondition::Equal(0x05),
+        ));
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Watchpoint {
+                address: 0xC123,
+                value: 0x05,
+                cycles: 8,
+            }
+        );
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+
+        cpu.remove_watchpoints(0xC123);
+        cpu.add_watchpoint(Watchpoint::new(0xC123));
+
+        assert_eq!(
+            cpu.run_until(StopCondition::Instructions(10)).unwrap(),
+            StopReason::Watchpoint {
+                address: 0xC123,
+                value: 0x06,
+            }
+        );
+        assert_eq!(cpu.registers.read(Register::PC), 0x0007);
+    }
+
+    #[test]
+    fn test_watchpoint_hit_outside_step_is_not_reported() {
+        let mut cpu = Cpu::new(&[0x00]);
+
+        cpu.add_watchpoint(Watchpoint::new(0xC000));
+        cpu.execute(Instruction::StoreStackPointerInMemory { address: 0xC000 })
+            .unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+    }
+
+    #[test]
+    fn test_value_conditions() {
+        assert!(ValueCondition::Equal(5).matches(5));
+        assert!(!ValueCondition::Equal(5).matches(6));
+        assert!(ValueCondition::NotEqual(5).matches(6));
+        assert!(!ValueCondition::NotEqual(5).matches(5));
+        assert!(ValueCondition::GreaterThan(5).matches(6));
+        assert!(!ValueCondition::GreaterThan(5).matches(5));
+        assert!(ValueCondition::LessThan(5).matches(4));
+        assert!(!ValueCondition::LessThan(5).matches(5));
+    }
+
+    #[test]
+    fn test_watchpoint_in_strict_mode_trap() {
+        let mut cpu = Cpu::new(&[0xE2, 0x00]);
+
+        cpu.set_strict_mode(true);
+        cpu.add_watchpoint(Watchpoint::new(0xFF26));
+        cpu.registers.write(Register::A, 0x80);
+        cpu.registers.write(Register::C, 0x26);
+
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::UnimplementedAccess {
+                addr: 0xFF26,
+                write: true,
+                cycles: 8,
+            }
+        );
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Watchpoint {
+                address: 0xFF26,
+                value: 0x80,
+                cycles: 0,
+            }
+        );
+        assert_eq!(cpu.registers.read(Register::PC), 0x0001);
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(4));
+    }
+
+    #[test]
+    fn test_vram_tracking() {
+        let mut cpu = Cpu::new(&[0x77, 0x77, 0x77]);
+
+        cpu.registers.write(Register::HL, 0x8012);
+        cpu.step().unwrap();
+
+        assert!(cpu.take_dirty_vram().is_empty());
+
+        cpu.enable_vram_tracking();
+        cpu.step().unwrap();
+
+        let dirty_vram = cpu.take_dirty_vram();
+
+        assert_eq!(
+            dirty_vram.tiles().iter().copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert!(dirty_vram.tile_map_entries().is_empty());
+        assert!(cpu.take_dirty_vram().is_empty());
+
+        cpu.registers.write(Register::HL, 0xC000);
+        cpu.step().unwrap();
+
+        assert!(cpu.take_dirty_vram().is_empty());
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let mut cpu = Cpu::new(&[0xF2, 0xE2, 0xE2, 0xF2]);
+
+        cpu.set_strict_mode(true);
+        cpu.registers.write(Register::C, 0x10);
+
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::UnimplementedAccess {
+                addr: 0xFF10,
+                write: false,
+                cycles: 8,
+            }
+        );
+
+        cpu.registers.write(Register::C, 0x26);
+
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::UnimplementedAccess {
+                addr: 0xFF26,
+                write: true,
+                cycles: 8,
+            }
+        );
+
+        cpu.registers.write(Register::C, 0x0F);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+
+        cpu.set_strict_mode(false);
+        cpu.registers.write(Register::C, 0x10);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+    }
+
+    #[test]
+    fn test_strict_mode_stops_run_until() {
+        let mut cpu = Cpu::new(&[0x00, 0xE2, 0x00]);
+
+        cpu.set_strict_mode(true);
+        cpu.registers.write(Register::C, 0x40);
+
+        assert_eq!(
+            cpu.run_until(StopCondition::Instructions(3)).unwrap(),
+            StopReason::UnimplementedAccess {
+                addr: 0xFF40,
+                write: true,
+                cycles: 12,
+            }
+        );
+    }
+
+    #[test]
+    fn test_profiler_finds_tight_loop() {
+        let mut program = vec![0x00; 0x0200];
+
+        program[0x0000..0x0003].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        program[0x0150..0x0155].copy_from_slice(&[0x00, 0x00, 0xC3, 0x50, 0x01]);
+
+        let mut cpu = Cpu::new(&program);
+
+        cpu.enable_profiler();
+
+        for _ in 0..100 {
+            cpu.step().unwrap();
+        }
+
+        let regions = cpu.profiler().unwrap().hottest_regions(2);
+
+        assert_eq!(regions, vec![(0x0100, 792), (0x0000, 16)]);
+        assert!(cpu.disable_profiler().is_some());
+        assert!(cpu.profiler().is_none());
+    }
+
+    #[test]
+    fn test_post_boot_state() {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x014D] = 0xE7;
+
+        let cpu = Cpu::new_post_boot(&rom);
+
+        assert_eq!(cpu.registers.read(Register::AF), 0x01B0);
+        assert_eq!(cpu.registers.read(Register::BC), 0x0013);
+        assert_eq!(cpu.registers.read(Register::DE), 0x00D8);
+        assert_eq!(cpu.registers.read(Register::HL), 0x014D);
+        assert_eq!(cpu.registers.read(Register::SP), 0xFFFE);
+        assert_eq!(cpu.registers.read(Register::PC), 0x0100);
+        assert_eq!(
+            cpu.state_string(),
+            "AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100 IME=0"
+        );
+    }
+
+    #[test]
+    fn test_post_boot_flags_with_zero_header_checksum() {
+        let cpu = Cpu::new_post_boot(&[0x00; 0x8000]);
+
+        assert_eq!(cpu.registers.read(Register::AF), 0x0180);
+    }
+
+    #[test]
+    fn test_post_boot_maps_only_rom_banks() {
+        let mut rom = vec![0x00; 0x10000];
+
+        rom[0x7FFF] = 0x42;
+        rom[0xC000] = 0x42;
+        rom[0xFFFF] = 0x1F;
+
+        let cpu = Cpu::new_post_boot(&rom);
+
+        assert_eq!(cpu.peek(0x7FFF), 0x42);
+        assert_eq!(cpu.peek(0xC000), 0x00);
+        assert_eq!(cpu.interrupt_flags(), 0xE1);
+        assert_eq!(cpu.interrupt_enable(), 0x00);
+    }
+
+    #[test]
+    fn test_current_instruction_is_side_effect_free() {
+        let cpu = Cpu::new(&[0x06, 0x42, 0x00]);
+
+        let first = cpu.current_instruction().unwrap();
+        let second = cpu.current_instruction().unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.1, "LD B,$42");
+        assert_eq!(cpu.registers.read(Register::PC), 0x0000);
+    }
+
+    #[test]
+    fn test_game_genie_patches_rom_reads() {
+        let mut rom = vec![0x00; 0x8000];
+
+        rom[0x4150] = 0x18;
+
+        let mut cpu = Cpu::new(&rom);
+
+        cpu.add_cheat("3E1-50B-8EA").unwrap();
+
+        assert_eq!(cpu.peek(0x4150), 0x3E);
+
+        cpu.memory[0x4150] = 0x19;
+
+        assert_eq!(cpu.peek(0x4150), 0x19);
+
+        cpu.memory[0x4150] = 0x18;
+
+        assert!(cpu.remove_cheat("3e1-50b-8ea"));
+        assert!(!cpu.remove_cheat("3E1-50B-8EA"));
+        assert_eq!(cpu.peek(0x4150), 0x18);
+        assert!(cpu.add_cheat("3E1-50B-8E").is_err());
+    }
+}
@@ -0,0 +1,44 @@
+const REGION_SIZE: usize = 0x100;
+const REGION_COUNT: usize = 0x10000 / REGION_SIZE;
+
+/// Attributes executed cycles to 256 byte regions of the address space, by the address of the
+/// instruction that consumed them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profiler {
+    cycles: Vec<u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            cycles: vec![0; REGION_COUNT],
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, cycles: u8) {
+        self.cycles[pc as usize / REGION_SIZE] += cycles as u64;
+    }
+
+    /// Returns up to `count` regions that consumed any cycles, as (start address, cycles),
+    /// hottest first.
+    pub fn hottest_regions(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut regions = self
+            .cycles
+            .iter()
+            .enumerate()
+            .filter(|(_, &cycles)| cycles > 0)
+            .map(|(region, &cycles)| ((region * REGION_SIZE) as u16, cycles))
+            .collect::<Vec<_>>();
+
+        regions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        regions.truncate(count);
+
+        regions
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Debug)]
+use crate::cpu::Flags;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     A,
     B,
@@ -15,3 +18,147 @@ pub enum Register {
     DE,
     HL,
 }
+
+impl Register {
+    pub fn width_in_bits(&self) -> u8 {
+        match self {
+            Register::A
+            | Register::B
+            | Register::C
+            | Register::D
+            | Register::E
+            | Register::F
+            | Register::H
+            | Register::L => 8,
+            Register::SP
+            | Register::PC
+            | Register::AF
+            | Register::BC
+            | Register::DE
+            | Register::HL => 16,
+        }
+    }
+
+    /// Whether this is one of the 16-bit registers made of two 8-bit ones. SP and PC aren't.
+    pub fn is_pair(&self) -> bool {
+        matches!(
+            self,
+            Register::AF | Register::BC | Register::DE | Register::HL
+        )
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Registers {
+    a: u8,
+    f: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    h: u8,
+    l: u8,
+    sp: u16,
+    pc: u16,
+}
+
+impl Registers {
+    pub fn read(&self, register: Register) -> u16 {
+        match register {
+            Register::A => self.a as u16,
+            Register::B => self.b as u16,
+            Register::C => self.c as u16,
+            Register::D => self.d as u16,
+            Register::E => self.e as u16,
+            Register::F => self.f as u16,
+            Register::H => self.h as u16,
+            Register::L => self.l as u16,
+            Register::SP => self.sp,
+            Register::PC => self.pc,
+            Register::AF => u16::from_be_bytes([self.a, self.f]),
+            Register::BC => u16::from_be_bytes([self.b, self.c]),
+            Register::DE => u16::from_be_bytes([self.d, self.e]),
+            Register::HL => u16::from_be_bytes([self.h, self.l]),
+        }
+    }
+
+    /// Writes `value` into `register`, truncating it for 8 bit registers.
+    ///
+    /// The lower nibble of F is hard-wired to zero, so it's masked out on every write to F or AF.
+    pub fn write(&mut self, register: Register, value: u16) {
+        let [high, low] = value.to_be_bytes();
+
+        match register {
+            Register::A => self.a = low,
+            Register::B => self.b = low,
+            Register::C => self.c = low,
+            Register::D => self.d = low,
+            Register::E => self.e = low,
+            Register::F => self.f = low & 0xF0,
+            Register::H => self.h = low,
+            Register::L => self.l = low,
+            Register::SP => self.sp = value,
+            Register::PC => self.pc = value,
+            Register::AF => {
+                self.a = high;
+                self.f = low & 0xF0;
+            }
+            Register::BC => {
+                self.b = high;
+                self.c = low;
+            }
+            Register::DE => {
+                self.d = high;
+                self.e = low;
+            }
+            Register::HL => {
+                self.h = high;
+                self.l = low;
+            }
+        }
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags::from(self.f)
+    }
+
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.f = flags.into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_widths() {
+        let registers = [
+            (Register::A, 8, false),
+            (Register::B, 8, false),
+            (Register::C, 8, false),
+            (Register::D, 8, false),
+            (Register::E, 8, false),
+            (Register::F, 8, false),
+            (Register::H, 8, false),
+            (Register::L, 8, false),
+            (Register::SP, 16, false),
+            (Register::PC, 16, false),
+            (Register::AF, 16, true),
+            (Register::BC, 16, true),
+            (Register::DE, 16, true),
+            (Register::HL, 16, true),
+        ];
+
+        for (register, width, is_pair) in registers {
+            assert_eq!(register.width_in_bits(), width, "{}", register);
+            assert_eq!(register.is_pair(), is_pair, "{}", register);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/// Copy of the whole 64KB address space at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemorySnapshot {
+    bytes: Vec<u8>,
+}
+
+impl MemorySnapshot {
+    pub(super) fn new(bytes: Vec<u8>) -> MemorySnapshot {
+        MemorySnapshot { bytes }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        self.bytes[address as usize]
+    }
+
+    /// Lists every address whose value differs, as (address, value in `self`, value in `other`).
+    pub fn diff(&self, other: &MemorySnapshot) -> Vec<(u16, u8, u8)> {
+        self.bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(address, (&before, &after))| (address as u16, before, after))
+            .collect()
+    }
+}
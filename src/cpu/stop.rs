@@ -0,0 +1,50 @@
+use crate::cpu::Cpu;
+use std::fmt::{self, Debug, Formatter};
+
+/// When `Cpu::run_until` should return.
+pub enum StopCondition {
+    /// At least this many cycles have elapsed.
+    Cycles(u64),
+    /// This many steps have been taken, counting interrupt dispatches as steps.
+    Instructions(u64),
+    /// PC reached the given address.
+    Pc(u16),
+    /// The closure returned true, checked after every step.
+    Custom(Box<dyn FnMut(&Cpu) -> bool>),
+}
+
+impl Debug for StopCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StopCondition::Cycles(cycles) => f.debug_tuple("Cycles").field(cycles).finish(),
+            StopCondition::Instructions(instructions) => {
+                f.debug_tuple("Instructions").field(instructions).finish()
+            }
+            StopCondition::Pc(address) => f.debug_tuple("Pc").field(address).finish(),
+            StopCondition::Custom(_) => f.debug_tuple("Custom").finish(),
+        }
+    }
+}
+
+/// Why `Cpu::run_until` returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Number of cycles that actually elapsed, which can overshoot the requested amount by part of
+    /// an instruction.
+    CyclesElapsed(u64),
+    InstructionsExecuted(u64),
+    PcReached(u16),
+    ConditionMet,
+    /// The out-of-bounds trap fired at this PC.
+    OutOfBounds(u16),
+    /// Strict mode caught an access to an unemulated I/O register, after `cycles` in total.
+    UnimplementedAccess {
+        addr: u16,
+        write: bool,
+        cycles: u64,
+    },
+    Watchpoint {
+        address: u16,
+        value: u8,
+    },
+}
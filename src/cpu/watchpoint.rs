@@ -0,0 +1,50 @@
+/// Predicate on the value written to a watched address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueCondition {
+    Equal(u8),
+    NotEqual(u8),
+    GreaterThan(u8),
+    LessThan(u8),
+}
+
+impl ValueCondition {
+    pub fn matches(&self, value: u8) -> bool {
+        match *self {
+            ValueCondition::Equal(expected) => value == expected,
+            ValueCondition::NotEqual(expected) => value != expected,
+            ValueCondition::GreaterThan(expected) => value > expected,
+            ValueCondition::LessThan(expected) => value < expected,
+        }
+    }
+}
+
+/// Breaks `Cpu::step` after an instruction writes to `address`, optionally only when the written
+/// value satisfies `condition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub address: u16,
+    pub condition: Option<ValueCondition>,
+}
+
+impl Watchpoint {
+    pub fn new(address: u16) -> Watchpoint {
+        Watchpoint {
+            address,
+            condition: None,
+        }
+    }
+
+    pub fn with_condition(address: u16, condition: ValueCondition) -> Watchpoint {
+        Watchpoint {
+            address,
+            condition: Some(condition),
+        }
+    }
+
+    pub(super) fn is_triggered_by(&self, address: u16, value: u8) -> bool {
+        self.address == address
+            && self
+                .condition
+                .is_none_or(|condition| condition.matches(value))
+    }
+}
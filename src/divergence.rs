@@ -0,0 +1,138 @@
+/// A field that differs between two trace lines. `None` on the side whose line lacks the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDelta {
+    pub name: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// The first point where two trace logs disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Zero-based index of the first differing line.
+    pub line: usize,
+    /// PC of the expected line, or of the actual one if the expected log ended first.
+    pub pc: Option<u16>,
+    pub deltas: Vec<FieldDelta>,
+}
+
+/// Finds the first line where `actual` diverges from `expected`.
+///
+/// Lines are made of whitespace-separated `NAME=VALUE` fields, as in `AF=01B0 BC=0013 PC=0100`,
+/// with PC in hexadecimal. A log ending early diverges at its first missing line.
+pub fn first_divergence<'a>(
+    expected: impl IntoIterator<Item = &'a str>,
+    actual: impl IntoIterator<Item = &'a str>,
+) -> Option<Divergence> {
+    let mut expected = expected.into_iter();
+    let mut actual = actual.into_iter();
+    let mut line = 0;
+
+    loop {
+        let (expected_line, actual_line) = (expected.next(), actual.next());
+
+        if expected_line.is_none() && actual_line.is_none() {
+            return None;
+        }
+
+        if expected_line.map(str::trim) != actual_line.map(str::trim) {
+            let expected_fields = fields(expected_line.unwrap_or_default());
+            let actual_fields = fields(actual_line.unwrap_or_default());
+            let pc_fields = if expected_line.is_some() {
+                &expected_fields
+            } else {
+                &actual_fields
+            };
+            let pc = pc_fields
+                .iter()
+                .find(|(name, _)| *name == "PC")
+                .and_then(|(_, value)| u16::from_str_radix(value, 16).ok());
+
+            return Some(Divergence {
+                line,
+                pc,
+                deltas: deltas(&expected_fields, &actual_fields),
+            });
+        }
+
+        line += 1;
+    }
+}
+
+fn fields(line: &str) -> Vec<(&str, &str)> {
+    line.split_whitespace()
+        .map(|field| field.split_once('=').unwrap_or((field, "")))
+        .collect()
+}
+
+fn deltas(expected: &[(&str, &str)], actual: &[(&str, &str)]) -> Vec<FieldDelta> {
+    let value = |fields: &[(&str, &str)], name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value.to_string())
+    };
+    let mut names: Vec<&str> = expected.iter().map(|(name, _)| *name).collect();
+
+    for (name, _) in actual {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| FieldDelta {
+            name: name.to_string(),
+            expected: value(expected, name),
+            actual: value(actual, name),
+        })
+        .filter(|delta| delta.expected != delta.actual)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: [&str; 3] = [
+        "AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100 IME=0",
+        "AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0101 IME=0",
+        "AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0150 IME=0",
+    ];
+
+    #[test]
+    fn test_injected_register_difference() {
+        let mut actual = TRACE;
+
+        actual[1] = "AF=01B0 BC=0014 DE=00D8 HL=014D SP=FFFE PC=0101 IME=0";
+
+        assert_eq!(
+            first_divergence(TRACE, actual),
+            Some(Divergence {
+                line: 1,
+                pc: Some(0x0101),
+                deltas: vec![FieldDelta {
+                    name: "BC".to_string(),
+                    expected: Some("0013".to_string()),
+                    actual: Some("0014".to_string()),
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_identical_logs() {
+        assert_eq!(first_divergence(TRACE, TRACE), None);
+    }
+
+    #[test]
+    fn test_truncated_log() {
+        let divergence = first_divergence(TRACE, TRACE[..2].iter().copied()).unwrap();
+
+        assert_eq!(divergence.line, 2);
+        assert_eq!(divergence.pc, Some(0x0150));
+        assert_eq!(divergence.deltas.len(), 7);
+        assert!(divergence.deltas.iter().all(|delta| delta.actual.is_none()));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::cpu::Instruction;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, OniError>;
+
+#[derive(Debug, Error)]
+pub enum OniError {
+    #[error("failed to decode instruction: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("invalid cartridge header: {0}")]
+    InvalidCartridgeHeader(&'static str),
+    #[error("unsupported cartridge type {0:#04X}")]
+    UnsupportedMbc(u8),
+    #[error("invalid ROM archive: {0}")]
+    InvalidRomArchive(String),
+    #[error("invalid cheat code {0:?}")]
+    InvalidCheatCode(String),
+    #[error("scale factor {0} is outside 1-6")]
+    InvalidScaleFactor(usize),
+    #[error("{length} bytes can't hold a {width}x{height} framebuffer")]
+    InvalidFramebufferSize {
+        width: usize,
+        height: usize,
+        length: usize,
+    },
+    #[error("instruction {0} is not implemented")]
+    UnimplementedInstruction(Instruction),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("illegal opcode {0:#04X}")]
+    IllegalOpcode(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(
+            Instruction::decode_strict(&mut Cursor::new(vec![0xD3])),
+            Err(OniError::Decode(DecodeError::IllegalOpcode(0xD3)))
+        ));
+        assert!(matches!(
+            Instruction::decode(&mut Cursor::new(vec![0xC3, 0x50])),
+            Err(OniError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_eyre_conversion() {
+        let report: eyre::Report = OniError::from(DecodeError::IllegalOpcode(0xD3)).into();
+
+        assert_eq!(
+            report.to_string(),
+            "failed to decode instruction: illegal opcode 0xD3"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The Game Boy's native refresh rate, in frames per second.
+pub const FRAME_RATE: f64 = 59.7275;
+
+/// A monotonic time source the `FrameLimiter` can sleep on.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed starting point.
+    fn now(&self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// A `Clock` backed by `Instant` and `thread::sleep`.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Paces a player loop to the native frame rate, scaled by a speed multiplier.
+///
+/// It lives outside the core, so headless users that run frames as fast as possible never pay
+/// for it.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter<C: Clock = SystemClock> {
+    clock: C,
+    speed: f64,
+    frame_start: Duration,
+}
+
+impl FrameLimiter<SystemClock> {
+    pub fn new() -> Self {
+        FrameLimiter::with_clock(SystemClock::default())
+    }
+}
+
+impl Default for FrameLimiter<SystemClock> {
+    fn default() -> Self {
+        FrameLimiter::new()
+    }
+}
+
+impl<C: Clock> FrameLimiter<C> {
+    pub fn with_clock(clock: C) -> Self {
+        let frame_start = clock.now();
+
+        FrameLimiter {
+            clock,
+            speed: 1.0,
+            frame_start,
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Sets the speed multiplier, e.g. 2.0 to run at twice the native frame rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` isn't a positive, finite number.
+    pub fn set_speed(&mut self, speed: f64) {
+        assert!(
+            speed.is_finite() && speed > 0.0,
+            "speed multiplier must be positive, got {}",
+            speed
+        );
+
+        self.speed = speed;
+    }
+
+    /// How long a single frame lasts at the current speed.
+    pub fn frame_period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / (FRAME_RATE * self.speed))
+    }
+
+    /// How long to sleep after spending `compute_time` on a frame. Never negative, a frame that
+    /// overran its period isn't slept on at all.
+    pub fn sleep_duration(&self, compute_time: Duration) -> Duration {
+        self.frame_period().saturating_sub(compute_time)
+    }
+
+    /// Sleeps for whatever is left of the current frame, then starts timing the next one.
+    ///
+    /// Call it once per frame, right after `run_frame`.
+    pub fn wait(&mut self) {
+        let compute_time = self.clock.now().saturating_sub(self.frame_start);
+        let sleep_duration = self.sleep_duration(compute_time);
+
+        if !sleep_duration.is_zero() {
+            self.clock.sleep(sleep_duration);
+        }
+
+        self.frame_start = self.clock.now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct FakeClock {
+        now: Duration,
+        sleeps: Vec<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+            self.now += duration;
+        }
+    }
+
+    fn run_frame(limiter: &mut FrameLimiter<FakeClock>, compute_time: Duration) {
+        limiter.clock.now += compute_time;
+        limiter.wait();
+    }
+
+    #[test]
+    fn test_sleeps_for_the_rest_of_the_frame() {
+        let mut limiter = FrameLimiter::with_clock(FakeClock::default());
+        let period = Duration::from_secs_f64(1.0 / FRAME_RATE);
+
+        run_frame(&mut limiter, Duration::from_millis(5));
+        run_frame(&mut limiter, Duration::from_millis(10));
+
+        assert_eq!(
+            limiter.clock.sleeps,
+            vec![
+                period - Duration::from_millis(5),
+                period - Duration::from_millis(10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_never_sleeps_after_an_overrun() {
+        let mut limiter = FrameLimiter::with_clock(FakeClock::default());
+
+        run_frame(&mut limiter, Duration::from_millis(20));
+
+        assert!(limiter.clock.sleeps.is_empty());
+        assert_eq!(
+            limiter.sleep_duration(Duration::from_millis(20)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_speed_multiplier() {
+        let mut limiter = FrameLimiter::with_clock(FakeClock::default());
+
+        limiter.set_speed(2.0);
+        run_frame(&mut limiter, Duration::from_millis(1));
+
+        assert_eq!(
+            limiter.clock.sleeps,
+            vec![Duration::from_secs_f64(1.0 / (FRAME_RATE * 2.0)) - Duration::from_millis(1)]
+        );
+    }
+}
@@ -1,4 +1,10 @@
+pub mod audio_buffer;
+pub mod cartridge;
 pub mod cpu;
+pub mod divergence;
+pub mod error;
+pub mod frame_limiter;
+pub mod scaler;
 
 fn main() {
     println!("Hello, world!");
@@ -0,0 +1,97 @@
+use crate::error::{OniError, Result};
+
+/// The scale factors `scale_framebuffer` accepts.
+pub const SCALE_FACTORS: std::ops::RangeInclusive<usize> = 1..=6;
+
+/// Upscales a `width`×`height` framebuffer with one byte per pixel by an integer `factor` using
+/// nearest-neighbor sampling, so every pixel becomes a `factor`×`factor` block.
+pub fn scale_framebuffer(
+    src: &[u8],
+    width: usize,
+    height: usize,
+    factor: usize,
+) -> Result<Vec<u8>> {
+    if !SCALE_FACTORS.contains(&factor) {
+        return Err(OniError::InvalidScaleFactor(factor));
+    }
+
+    if width.checked_mul(height) != Some(src.len()) {
+        return Err(OniError::InvalidFramebufferSize {
+            width,
+            height,
+            length: src.len(),
+        });
+    }
+
+    let mut scaled = Vec::with_capacity(src.len() * factor * factor);
+
+    for y in 0..height {
+        let row = &src[y * width..(y + 1) * width];
+        let mut scaled_row = Vec::with_capacity(width * factor);
+
+        for &pixel in row {
+            scaled_row.extend(std::iter::repeat_n(pixel, factor));
+        }
+
+        for _ in 0..factor {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+
+    Ok(scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_by_three() {
+        let scaled = scale_framebuffer(&[0, 1, 2, 3], 2, 2, 3).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            scaled,
+            vec![
+                0, 0, 0, 1, 1, 1,
+                0, 0, 0, 1, 1, 1,
+                0, 0, 0, 1, 1, 1,
+                2, 2, 2, 3, 3, 3,
+                2, 2, 2, 3, 3, 3,
+                2, 2, 2, 3, 3, 3,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scale_by_one_is_identity() {
+        assert_eq!(
+            scale_framebuffer(&[0, 1, 2, 3, 0, 1], 3, 2, 1).unwrap(),
+            vec![0, 1, 2, 3, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_invalid_factors() {
+        assert!(matches!(
+            scale_framebuffer(&[0; 4], 2, 2, 0),
+            Err(OniError::InvalidScaleFactor(0))
+        ));
+        assert!(matches!(
+            scale_framebuffer(&[0; 4], 2, 2, 7),
+            Err(OniError::InvalidScaleFactor(7))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        assert!(matches!(
+            scale_framebuffer(&[0; 5], 2, 2, 2),
+            Err(OniError::InvalidFramebufferSize {
+                width: 2,
+                height: 2,
+                length: 5,
+            })
+        ));
+    }
+}